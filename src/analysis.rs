@@ -0,0 +1,217 @@
+// Analysis of a signal sampled over a single period
+use crate::signals::{self, Edge, Signal};
+use nannou::prelude::*;
+
+/// The number of samples taken over the period by default.
+pub const DEFAULT_RESOLUTION: usize = 1024;
+/// The number of harmonics measured for the spectrum, starting at the fundamental.
+pub const NUM_HARMONICS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub rms: f32,
+    // How far the signal travels beyond the -1..1 range, 0 if it stays within it
+    pub overshoot: f32,
+    // The jumps in the signal, found just as the band-limited signals find them
+    pub discontinuities: Vec<Edge>,
+    // The amplitude of each harmonic, starting at the fundamental
+    pub harmonics: Vec<f32>,
+}
+
+impl Analysis {
+    /// Analyse the given signal, taking `resolution` samples over the period.
+    pub fn new(signal: &Signal, resolution: usize) -> Self {
+        let resolution = resolution.max(2);
+        let phase = |i: usize| i as f32 / resolution as f32;
+        let samples: Vec<f32> = (0..resolution).map(|i| signal.amp(phase(i))).collect();
+
+        let min = samples.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = samples.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let mean = samples.iter().sum::<f32>() / resolution as f32;
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / resolution as f32).sqrt();
+        let overshoot = (max - 1.0).max(-1.0 - min).max(0.0);
+
+        let discontinuities = signals::find_edges(signal);
+
+        // The amplitude of each harmonic from a discrete fourier transform of the samples.
+        let harmonics = (1..=NUM_HARMONICS)
+            .map(|k| {
+                let (re, im) = samples
+                    .iter()
+                    .enumerate()
+                    .fold((0.0, 0.0), |(re, im), (i, s)| {
+                        let angle = TAU * k as f32 * phase(i);
+                        (re + s * angle.cos(), im - s * angle.sin())
+                    });
+                (re * re + im * im).sqrt() * 2.0 / resolution as f32
+            })
+            .collect();
+
+        Analysis {
+            min,
+            max,
+            mean,
+            rms,
+            overshoot,
+            discontinuities,
+            harmonics,
+        }
+    }
+
+    /// Scale the given amplitude of the analysed signal so that its full range fits within
+    /// -1..1. Signals that don't overshoot are left untouched.
+    pub fn normalise(&self, amp: f32) -> f32 {
+        if self.overshoot <= 0.0 {
+            return amp;
+        }
+        map_range(amp, self.min.min(-1.0), self.max.max(1.0), -1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals;
+
+    // The analysis of each of `signals::ALL` at the default resolution: the name, min, max, mean
+    // and RMS, followed by the number of discontinuities and the total size of their jumps.
+    const PINNED: &[(&str, f32, f32, f32, f32, usize, f32)] = &[
+        ("lfo.sine", -1.0, 1.0, 0.0, 0.707, 0, 0.0),
+        ("lfo.triangle", -1.0, 1.0, 0.0, 0.577, 0, 0.0),
+        ("lfo.sawtooth", -0.998, 1.0, 0.001, 0.577, 1, 2.0),
+        ("lfo.square", -1.0, 1.0, 0.0, 1.0, 2, 4.0),
+        ("noise.value", -1.0, 0.732, -0.314, 0.625, 0, 0.0),
+        ("noise.perlin", -0.664, 0.833, 0.0, 0.25, 0, 0.0),
+        (
+            "noise.sample_and_hold",
+            -1.0,
+            0.732,
+            -0.314,
+            0.669,
+            16,
+            7.678,
+        ),
+        ("noise.random_walk", -0.998, 0.0, -0.606, 0.639, 0, 0.0),
+        ("ease.back_in", -1.2, 0.991, -0.785, 0.962, 1, 2.0),
+        ("ease.back_in_out", -1.2, 1.2, -0.001, 1.008, 1, 2.0),
+        ("ease.back_in_out_s", -1.055, 1.055, -0.001, 0.896, 1, 2.0),
+        ("ease.back_in_s", -1.047, 0.993, -0.634, 0.843, 1, 2.0),
+        ("ease.back_out", -1.0, 1.2, 0.783, 0.962, 1, 2.0),
+        ("ease.back_out_s", -1.0, 1.047, 0.632, 0.843, 1, 2.0),
+        ("ease.bounce_in", -1.0, 1.0, -0.378, 0.761, 1, 2.0),
+        ("ease.bounce_in_out", -1.0, 0.999, -0.001, 0.764, 1, 2.0),
+        ("ease.bounce_out", -1.0, 1.0, 0.376, 0.761, 1, 2.0),
+        ("ease.circ_in", -1.0, 0.912, -0.572, 0.725, 1, 1.999),
+        ("ease.circ_in_out", -1.0, 1.0, -0.001, 0.817, 1, 2.0),
+        ("ease.circ_out", -1.0, 1.0, 0.57, 0.725, 1, 2.0),
+        ("ease.cubic_in", -1.0, 0.994, -0.501, 0.756, 1, 2.0),
+        ("ease.cubic_in_out", -1.0, 1.0, -0.001, 0.802, 1, 2.0),
+        ("ease.cubic_out", -1.0, 1.0, 0.499, 0.756, 1, 2.0),
+        ("ease.elastic_in", -1.746, 0.986, -0.972, 1.05, 1, 2.0),
+        ("ease.elastic_in_out", -1.237, 1.237, -0.001, 0.993, 1, 2.0),
+        ("ease.elastic_out", -1.0, 1.746, 0.971, 1.05, 1, 2.001),
+        ("ease.expo_in", -1.0, 0.987, -0.713, 0.844, 2, 2.002),
+        ("ease.expo_in_out", -1.0, 0.999, -0.001, 0.885, 1, 1.999),
+        ("ease.expo_out", -1.0, 0.998, 0.711, 0.844, 1, 1.998),
+        ("ease.quad_in", -1.0, 0.996, -0.334, 0.683, 1, 2.0),
+        ("ease.quad_in_out", -1.0, 1.0, -0.001, 0.73, 1, 2.0),
+        ("ease.quad_out", -1.0, 1.0, 0.332, 0.683, 1, 2.0),
+        ("ease.quart_in", -1.0, 0.992, -0.601, 0.803, 1, 2.0),
+        ("ease.quart_in_out", -1.0, 1.0, -0.001, 0.843, 1, 2.0),
+        ("ease.quart_out", -1.0, 1.0, 0.599, 0.803, 1, 2.0),
+        ("ease.quint_in", -1.0, 0.99, -0.668, 0.835, 1, 2.0),
+        ("ease.quint_in_out", -1.0, 1.0, -0.001, 0.87, 1, 2.0),
+        ("ease.quint_out", -1.0, 1.0, 0.666, 0.835, 1, 2.0),
+        ("ease.sine_in", -1.0, 0.997, -0.274, 0.673, 1, 2.0),
+        ("ease.sine_in_out", -1.0, 1.0, -0.001, 0.707, 1, 2.0),
+        ("ease.sine_out", -1.0, 1.0, 0.272, 0.673, 1, 2.0),
+        ("ease.power_in", -1.0, 0.996, -0.334, 0.683, 1, 2.0),
+        ("ease.power_in_out", -1.0, 1.0, -0.001, 0.73, 1, 2.0),
+        ("ease.power_out", -1.0, 1.0, 0.332, 0.683, 1, 2.0),
+        ("ease.cubic_bezier", -1.0, 1.0, 0.337, 0.746, 1, 2.0),
+        ("curve", -1.0, 1.0, 0.124, 0.637, 1, 1.0),
+        ("rhythm.euclidean", -1.0, 1.0, -0.625, 1.0, 6, 12.0),
+        ("rhythm.sequencer", -1.0, 1.0, -0.062, 0.707, 12, 14.0),
+    ];
+
+    #[test]
+    fn sine_rms_is_one_over_root_two() {
+        let analysis = Analysis::new(&Signal::SINE, DEFAULT_RESOLUTION);
+        assert!(
+            (analysis.rms - 0.5f32.sqrt()).abs() < 1e-3,
+            "{}",
+            analysis.rms
+        );
+        assert!(analysis.mean.abs() < 1e-3);
+        assert!(analysis.discontinuities.is_empty());
+        assert!((analysis.harmonics[0] - 1.0).abs() < 1e-3);
+        assert!(analysis.harmonics[1..].iter().all(|h| *h < 1e-3));
+    }
+
+    #[test]
+    fn saw_has_a_single_discontinuity() {
+        let analysis = Analysis::new(&Signal::SAWTOOTH, DEFAULT_RESOLUTION);
+        assert_eq!(
+            analysis.discontinuities.len(),
+            1,
+            "{:?}",
+            analysis.discontinuities
+        );
+        let jump = analysis.discontinuities[0];
+        assert!(
+            jump.phase < 1e-3 || jump.phase > 1.0 - 1e-3,
+            "{}",
+            jump.phase
+        );
+        assert!((jump.jump.abs() - 2.0).abs() < 0.05, "{}", jump.jump);
+    }
+
+    #[test]
+    fn built_in_signals_are_within_their_range() {
+        for signal in signals::ALL {
+            let analysis = Analysis::new(signal, DEFAULT_RESOLUTION);
+            let Analysis {
+                min,
+                max,
+                mean,
+                rms,
+                overshoot,
+                ..
+            } = analysis;
+            assert!(min.is_finite() && max.is_finite(), "{}", signal);
+            assert!(min <= mean && mean <= max, "{}", signal);
+            assert!(rms <= min.abs().max(max.abs()) + 1e-6, "{}", signal);
+            assert_eq!(overshoot > 0.0, min < -1.0 || max > 1.0, "{}", signal);
+
+            // Normalising brings the whole signal within -1..1.
+            for i in 0..DEFAULT_RESOLUTION {
+                let amp = analysis.normalise(signal.amp(i as f32 / DEFAULT_RESOLUTION as f32));
+                assert!(
+                    (-1.0 - 1e-6..=1.0 + 1e-6).contains(&amp),
+                    "{} {}",
+                    signal,
+                    amp
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn built_in_signals_match_their_pinned_analysis() {
+        assert_eq!(PINNED.len(), signals::ALL.len());
+        for (signal, pinned) in signals::ALL.iter().zip(PINNED) {
+            let &(name, min, max, mean, rms, edges, jumps) = pinned;
+            assert_eq!(signal.to_string(), name);
+            let a = Analysis::new(signal, DEFAULT_RESOLUTION);
+            let total: f32 = a.discontinuities.iter().map(|d| d.jump.abs()).sum();
+            let found = [a.min, a.max, a.mean, a.rms, total];
+            for (found, pinned) in found.iter().zip(&[min, max, mean, rms, jumps]) {
+                assert!((found - pinned).abs() < 1e-3, "{} {:?}", name, found);
+            }
+            assert_eq!(a.discontinuities.len(), edges, "{}", name);
+        }
+    }
+}
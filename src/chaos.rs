@@ -0,0 +1,202 @@
+// Chaotic phase sources
+//
+// Each oscillator runs its own copy of a chaotic system, started from initial conditions that
+// differ very slightly along the strip. Chaos amplifies the difference, so the oscillators start
+// out together and gradually drift apart, never repeating.
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Each system with its classic chaotic parameters.
+pub const ALL: &[System] = &[
+    System::Lorenz {
+        sigma: 10.0,
+        rho: 28.0,
+        beta: 8.0 / 3.0,
+    },
+    System::Rossler {
+        a: 0.2,
+        b: 0.2,
+        c: 5.7,
+    },
+    System::Logistic { r: 3.9 },
+];
+
+/// The default number of time units of the system simulated per second.
+pub const DEFAULT_RATE: f32 = 0.25;
+/// The default difference in initial conditions from the first oscillator to the last.
+pub const DEFAULT_SPREAD: f32 = 0.01;
+
+// The largest step the continuous systems are integrated by, in the system's own time units.
+const MAX_STEP: f32 = 0.01;
+// Longer frames than this, such as after a stall, are simulated as this long.
+const MAX_DT: f32 = 0.25;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum System {
+    Lorenz { sigma: f32, rho: f32, beta: f32 },
+    Rossler { a: f32, b: f32, c: f32 },
+    // The discrete `x = r * x * (1 - x)` map, iterated once per time unit
+    Logistic { r: f32 },
+}
+
+pub struct Chaos {
+    system: System,
+    // The number of time units of the system simulated per second
+    pub rate: f32,
+    // The difference in initial conditions from the first oscillator to the last
+    spread: f32,
+    // The state of each oscillator's copy of the system
+    states: Vec<[f32; 3]>,
+    // Time units towards the next iteration of the logistic map, 0..1
+    iteration: f32,
+    levels: Vec<f32>,
+}
+
+impl System {
+    pub fn name(&self) -> &'static str {
+        match self {
+            System::Lorenz { .. } => "Lorenz",
+            System::Rossler { .. } => "Rossler",
+            System::Logistic { .. } => "Logistic",
+        }
+    }
+
+    // A point on or near the attractor to start from.
+    fn initial(&self) -> [f32; 3] {
+        match self {
+            System::Lorenz { .. } => [1.0, 1.0, 20.0],
+            System::Rossler { .. } => [1.0, 1.0, 0.0],
+            System::Logistic { .. } => [0.4, 0.4, 0.0],
+        }
+    }
+
+    // The rate of change of the continuous systems at the given state.
+    fn derivative(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        match *self {
+            System::Lorenz { sigma, rho, beta } => {
+                [sigma * (y - x), x * (rho - z) - y, x * y - beta * z]
+            }
+            System::Rossler { a, b, c } => [-y - z, x + a * y, b + z * (x - c)],
+            System::Logistic { .. } => [0.0; 3],
+        }
+    }
+
+    // Advance a continuous system by `h` time units with a fourth order Runge-Kutta step.
+    fn step(&self, s: [f32; 3], h: f32) -> [f32; 3] {
+        let add =
+            |a: [f32; 3], b: [f32; 3], k: f32| [a[0] + b[0] * k, a[1] + b[1] * k, a[2] + b[2] * k];
+        let k1 = self.derivative(s);
+        let k2 = self.derivative(add(s, k1, h * 0.5));
+        let k3 = self.derivative(add(s, k2, h * 0.5));
+        let k4 = self.derivative(add(s, k3, h));
+        let mut next = s;
+        for i in 0..3 {
+            next[i] += h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+        }
+        next
+    }
+
+    // Map the x coordinate of the state into -1..1 from the range the attractor covers.
+    fn normalise(&self, s: [f32; 3], iteration: f32) -> f32 {
+        let amp = match self {
+            System::Lorenz { .. } => s[0] / 20.0,
+            System::Rossler { .. } => (s[0] - 1.0) / 11.0,
+            // Glide from the previous iterate to the current one.
+            System::Logistic { .. } => (s[1] + (s[0] - s[1]) * iteration) * 2.0 - 1.0,
+        };
+        amp.clamp(-1.0, 1.0)
+    }
+}
+
+impl Chaos {
+    pub fn new(system: System) -> Self {
+        Chaos {
+            system,
+            rate: DEFAULT_RATE,
+            spread: DEFAULT_SPREAD,
+            states: Vec::new(),
+            iteration: 0.0,
+            levels: Vec::new(),
+        }
+    }
+
+    pub fn system(&self) -> System {
+        self.system
+    }
+
+    /// Switch to the given system, restarting every oscillator.
+    pub fn set_system(&mut self, system: System) {
+        self.system = system;
+        self.reset();
+    }
+
+    pub fn spread(&self) -> f32 {
+        self.spread
+    }
+
+    /// Set the spread of initial conditions, restarting every oscillator.
+    pub fn set_spread(&mut self, spread: f32) {
+        self.spread = spread;
+        self.reset();
+    }
+
+    /// Restart every oscillator from its initial condition.
+    pub fn reset(&mut self) {
+        self.states.clear();
+    }
+
+    /// Advance every oscillator by `dt`, starting any new oscillators so that there are `size`.
+    pub fn update(&mut self, size: usize, dt: Duration) {
+        if self.states.len() != size {
+            let system = self.system;
+            let initial = system.initial();
+            let spread = self.spread;
+            let start = self.states.len().min(size);
+            self.states.truncate(size);
+            self.states.extend((start..size).map(|i| {
+                let offset = spread * i as f32 / size as f32;
+                let mut state = initial;
+                state[0] += offset;
+                if let System::Logistic { .. } = system {
+                    state[1] = state[0];
+                }
+                state
+            }));
+        }
+
+        let t = (dt.as_secs_f64() as f32).min(MAX_DT) * self.rate.max(0.0);
+        let system = self.system;
+        match system {
+            System::Logistic { r } => {
+                self.iteration += t;
+                while self.iteration >= 1.0 {
+                    self.iteration -= 1.0;
+                    for s in self.states.iter_mut() {
+                        // Keep the previous iterate to glide from.
+                        s[1] = s[0];
+                        s[0] = r * s[0] * (1.0 - s[0]);
+                    }
+                }
+            }
+            _ => {
+                let steps = (t / MAX_STEP).ceil().max(1.0);
+                let h = t / steps;
+                for s in self.states.iter_mut() {
+                    for _ in 0..steps as usize {
+                        *s = system.step(*s, h);
+                    }
+                }
+            }
+        }
+
+        let iteration = self.iteration;
+        self.levels.clear();
+        let levels = self.states.iter().map(|s| system.normalise(*s, iteration));
+        self.levels.extend(levels);
+    }
+
+    /// The output of each oscillator, -1..1
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+}
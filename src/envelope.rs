@@ -0,0 +1,189 @@
+// One-shot envelopes fired by triggers
+//
+// Unlike the signals, which loop with the shm phase, an envelope runs once from each trigger
+// and then falls silent. Each oscillator along the strip hears the trigger a little later than
+// the one before it, by the shm `a_velocity`, so a hit travels along the strip as a pulse.
+use crate::signals::{self, EasingType, Signal};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How many seconds an `a_velocity` offset of one cycle delays the envelope by.
+pub const SECONDS_PER_CYCLE: f32 = 1.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    // Attack, decay and release in seconds, with the sustain level held until the gate closes
+    // `gate` seconds after the trigger
+    Adsr {
+        attack: f32,
+        decay: f32,
+        sustain: f32,
+        gate: f32,
+        release: f32,
+    },
+    // Attack, hold at the peak and decay, in seconds
+    Ahd {
+        attack: f32,
+        hold: f32,
+        decay: f32,
+    },
+    // Fall from the peak to silence along the easing over the duration in seconds
+    Ease {
+        ease_type: EasingType,
+        duration: f32,
+    },
+}
+
+pub struct Envelope {
+    pub shape: Shape,
+    // The seconds since each trigger that is still sounding somewhere along the strip
+    hits: Vec<f32>,
+    // The output of each oscillator, -1..1
+    levels: Vec<f32>,
+}
+
+impl Shape {
+    pub const ADSR: Self = Shape::Adsr {
+        attack: 0.05,
+        decay: 0.2,
+        sustain: 0.6,
+        gate: 0.5,
+        release: 0.5,
+    };
+    pub const AHD: Self = Shape::Ahd {
+        attack: 0.05,
+        hold: 0.1,
+        decay: 0.4,
+    };
+
+    /// The name shown in the GUI, the easing's name for the easing envelopes
+    pub fn name(&self) -> String {
+        match self {
+            Shape::Adsr { .. } => "ADSR".to_string(),
+            Shape::Ahd { .. } => "AHD".to_string(),
+            Shape::Ease { ease_type, .. } => ease_type.id(),
+        }
+    }
+
+    /// The seconds from the trigger until the envelope falls silent
+    pub fn duration(&self) -> f32 {
+        match *self {
+            Shape::Adsr { gate, release, .. } => gate + release,
+            Shape::Ahd {
+                attack,
+                hold,
+                decay,
+            } => attack + hold + decay,
+            Shape::Ease { duration, .. } => duration,
+        }
+    }
+
+    /// The level of the envelope `t` seconds after the trigger, 0..1
+    pub fn level(&self, t: f32) -> f32 {
+        if t < 0.0 || t >= self.duration() {
+            return 0.0;
+        }
+        match *self {
+            Shape::Adsr {
+                attack,
+                decay,
+                sustain,
+                gate,
+                release,
+            } => {
+                let held = |t: f32| {
+                    if t < attack {
+                        t / attack
+                    } else if t < attack + decay {
+                        1.0 - (1.0 - sustain) * (t - attack) / decay
+                    } else {
+                        sustain
+                    }
+                };
+                if t < gate {
+                    held(t)
+                } else {
+                    // Release from wherever the envelope was when the gate closed.
+                    held(gate) * (1.0 - (t - gate) / release)
+                }
+            }
+            Shape::Ahd {
+                attack,
+                hold,
+                decay,
+            } => {
+                if t < attack {
+                    t / attack
+                } else if t < attack + hold {
+                    1.0
+                } else {
+                    1.0 - (t - attack - hold) / decay
+                }
+            }
+            Shape::Ease {
+                ease_type,
+                duration,
+            } => 1.0 - signals::ease_lfo(ease_type, t / duration),
+        }
+    }
+}
+
+/// The ADSR and AHD envelopes followed by an easing envelope for each of the easings.
+pub fn shapes() -> Vec<Shape> {
+    let eases = signals::ALL.iter().filter_map(|signal| match signal {
+        Signal::Ease(ease_type) => Some(Shape::Ease {
+            ease_type: *ease_type,
+            duration: 1.0,
+        }),
+        _ => None,
+    });
+    vec![Shape::ADSR, Shape::AHD]
+        .into_iter()
+        .chain(eases)
+        .collect()
+}
+
+impl Envelope {
+    pub fn new(shape: Shape) -> Self {
+        Envelope {
+            shape,
+            hits: Vec::new(),
+            levels: Vec::new(),
+        }
+    }
+
+    /// Fire the envelope from the start of the strip.
+    pub fn trigger(&mut self) {
+        self.hits.push(0.0);
+    }
+
+    /// Advance every hit by `dt`, delaying each of the `size` oscillators by `a_velocity` from
+    /// the one before it.
+    pub fn update(&mut self, size: usize, a_velocity: f32, dt: Duration) {
+        let dt = dt.as_secs_f64() as f32;
+        let delay = a_velocity.abs() * SECONDS_PER_CYCLE;
+
+        // Forget the hits that have passed the end of the strip.
+        let end = self.shape.duration() + delay * size as f32;
+        for t in self.hits.iter_mut() {
+            *t += dt;
+        }
+        self.hits.retain(|&t| t < end);
+
+        let shape = self.shape;
+        let hits = &self.hits;
+        self.levels.clear();
+        self.levels.extend((0..size).map(|i| {
+            let level = hits
+                .iter()
+                .map(|t| shape.level(t - delay * i as f32))
+                .fold(0.0, f32::max);
+            level * 2.0 - 1.0
+        }));
+    }
+
+    /// The level of each oscillator, -1..1
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+}
@@ -0,0 +1,497 @@
+// Signals defined by a text formula, such as `sin(p * 2) * 0.5 + tri(p + 0.25) * 0.5`
+//
+// The formula is parsed once into an expression tree which is then evaluated for every phase.
+// `p` is the phase in cycles and `pi` is the usual constant. The LFOs and easings are available
+// by their snake case names, e.g. `triangle(p)` or `sine_in_out(p)`, along with the short LFO
+// aliases `sin`, `tri`, `saw` and `sqr`. Like the signals themselves, they take a phase in cycles
+// and return a value in the range -1..1.
+use crate::names::snake_case;
+use crate::signals::{self, EasingType, LfoType, Signal};
+use nannou::math::fmod;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+
+// How deeply a formula may nest, so that parsing and evaluating it can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Formula {
+    // The text the formula was parsed from
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Const(f32),
+    Phase,
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Func {
+    Lfo(LfoType),
+    Ease(EasingType),
+    Abs,
+    Floor,
+    Fract,
+    Sqrt,
+    Min,
+    Max,
+    Pow,
+    Clamp,
+    Mix,
+}
+
+/// The reason a formula failed to parse, along with the position of the character it occurred
+/// at, counting from 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Formula {
+    /// Parse the given formula
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        // Errors are found at byte offsets, but reported by character.
+        let expr = parse(source).map_err(|err| ParseError {
+            position: source[..err.position].chars().count(),
+            ..err
+        })?;
+        Ok(Formula {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// The text the formula was parsed from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn amp(&self, phase: f32) -> f32 {
+        self.expr.eval(phase)
+    }
+}
+
+impl Expr {
+    fn eval(&self, phase: f32) -> f32 {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Phase => phase,
+            Expr::Neg(expr) => -expr.eval(phase),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(phase), b.eval(phase));
+                match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div => a / b,
+                    BinOp::Rem => fmod(a, b),
+                    BinOp::Pow => a.powf(b),
+                }
+            }
+            Expr::Call(func, args) => {
+                let arg = |i: usize| args[i].eval(phase);
+                match func {
+                    Func::Lfo(lfo_type) => lfo_type.amp(arg(0)),
+                    Func::Ease(ease_type) => ease_type.amp(arg(0)),
+                    Func::Abs => arg(0).abs(),
+                    Func::Floor => arg(0).floor(),
+                    Func::Fract => fmod(arg(0), 1.0),
+                    Func::Sqrt => arg(0).sqrt(),
+                    Func::Min => arg(0).min(arg(1)),
+                    Func::Max => arg(0).max(arg(1)),
+                    Func::Pow => arg(0).powf(arg(1)),
+                    Func::Clamp => arg(0).max(arg(1)).min(arg(2)),
+                    Func::Mix => {
+                        let (a, b) = (arg(0), arg(1));
+                        a + (b - a) * arg(2)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Func {
+    // Look up a function by name, returning it along with the number of arguments it takes.
+    fn from_name(name: &str) -> Option<(Self, usize)> {
+        let func = match name {
+            "sin" => Func::Lfo(LfoType::Sine),
+            "tri" => Func::Lfo(LfoType::Triangle),
+            "saw" => Func::Lfo(LfoType::Sawtooth),
+            "sqr" => Func::Lfo(LfoType::Square { pulse_width: 0.5 }),
+            "abs" => Func::Abs,
+            "floor" => Func::Floor,
+            "fract" => Func::Fract,
+            "sqrt" => Func::Sqrt,
+            "min" => Func::Min,
+            "max" => Func::Max,
+            "pow" => Func::Pow,
+            "clamp" => Func::Clamp,
+            "mix" => Func::Mix,
+            _ => {
+                return signals::ALL.iter().find_map(|signal| match signal {
+                    Signal::Lfo(lfo_type) if snake_case(lfo_type.name()) == name => {
+                        Some((Func::Lfo(*lfo_type), 1))
+                    }
+                    Signal::Ease(ease_type) if snake_case(ease_type.name()) == name => {
+                        Some((Func::Ease(*ease_type), 1))
+                    }
+                    _ => None,
+                })
+            }
+        };
+        let arity = match func {
+            Func::Min | Func::Max | Func::Pow => 2,
+            Func::Clamp | Func::Mix => 3,
+            _ => 1,
+        };
+        Some((func, arity))
+    }
+}
+
+fn parse(source: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        end: source.len(),
+        depth: 0,
+    };
+    let expr = parser.expr()?;
+    if let Some(&(position, _)) = parser.tokens.get(parser.pos) {
+        return Err(ParseError::new(position, "unexpected input"));
+    }
+    Ok(expr)
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = i;
+                while let Some(&(j, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                let number = source[i..end]
+                    .parse()
+                    .map_err(|_| ParseError::new(i, "invalid number"))?;
+                tokens.push((i, Token::Number(number)));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push((i, Token::Ident(ident)));
+                continue;
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => Token::Op(c),
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            _ => return Err(ParseError::new(i, format!("unexpected character '{}'", c))),
+        };
+        tokens.push((i, token));
+        chars.next();
+    }
+    Ok(tokens)
+}
+
+// A recursive descent parser over the tokens, lowest precedence first.
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    pos: usize,
+    // The position reported for errors at the end of the input
+    end: usize,
+    // How many expressions deep the parser is
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|&(i, _)| i)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t);
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), ParseError> {
+        let position = self.position();
+        match self.next() {
+            Some(token) if *token == expected => Ok(()),
+            _ => Err(ParseError::new(position, format!("expected {}", what))),
+        }
+    }
+
+    // Go one expression deeper, failing once the formula nests past `MAX_DEPTH`. Each rule
+    // returns to the depth it started at once it has parsed its expression.
+    fn deepen(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        match self.depth > MAX_DEPTH {
+            true => Err(ParseError::new(self.position(), "formula nests too deeply")),
+            false => Ok(()),
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut expr = self.term()?;
+        while let Some(&Token::Op(c)) = self.peek() {
+            let op = match c {
+                '+' => BinOp::Add,
+                '-' => BinOp::Sub,
+                _ => break,
+            };
+            self.pos += 1;
+            self.deepen()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.term()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut expr = self.unary()?;
+        while let Some(&Token::Op(c)) = self.peek() {
+            let op = match c {
+                '*' => BinOp::Mul,
+                '/' => BinOp::Div,
+                '%' => BinOp::Rem,
+                _ => break,
+            };
+            self.pos += 1;
+            self.deepen()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(&Token::Op('-')) = self.peek() {
+            let depth = self.depth;
+            self.pos += 1;
+            self.deepen()?;
+            let expr = self.unary()?;
+            self.depth = depth;
+            return Ok(Expr::Neg(Box::new(expr)));
+        }
+        self.power()
+    }
+
+    // power := atom ('^' unary)?
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let base = self.atom()?;
+        if let Some(&Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            self.deepen()?;
+            let exponent = self.unary()?;
+            self.depth = depth;
+            return Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    // atom := number | 'p' | 'pi' | ident '(' args ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        let depth = self.depth;
+        match self.next().cloned() {
+            Some(Token::Number(value)) => Ok(Expr::Const(value)),
+            Some(Token::LParen) => {
+                self.deepen()?;
+                let expr = self.expr()?;
+                self.expect(Token::RParen, "')'")?;
+                self.depth = depth;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "p" => Ok(Expr::Phase),
+                "pi" => Ok(Expr::Const(PI)),
+                _ => {
+                    let (func, arity) = Func::from_name(&name).ok_or_else(|| {
+                        ParseError::new(position, format!("unknown function '{}'", name))
+                    })?;
+                    self.expect(Token::LParen, "'(' after function name")?;
+                    self.deepen()?;
+                    let mut args = vec![self.expr()?];
+                    while let Some(Token::Comma) = self.peek() {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                    self.expect(Token::RParen, "')'")?;
+                    self.depth = depth;
+                    if args.len() != arity {
+                        let msg = format!("'{}' takes {} argument(s)", name, arity);
+                        return Err(ParseError::new(position, msg));
+                    }
+                    Ok(Expr::Call(func, args))
+                }
+            },
+            _ => Err(ParseError::new(position, "expected a value")),
+        }
+    }
+}
+
+impl ParseError {
+    fn new<S: Into<String>>(position: usize, message: S) -> Self {
+        ParseError {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::convert::TryFrom<String> for Formula {
+    type Error = ParseError;
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Formula::parse(&source)
+    }
+}
+
+impl From<Formula> for String {
+    fn from(formula: Formula) -> Self {
+        formula.source
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str) -> f32 {
+        match Formula::parse(source) {
+            Ok(formula) => formula.amp(0.25),
+            Err(err) => panic!("{} failed to parse: {}", source, err),
+        }
+    }
+
+    fn error(source: &str) -> ParseError {
+        match Formula::parse(source) {
+            Ok(_) => panic!("{} should not parse", source),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_eq!(eval("-2^2"), -4.0);
+        assert_eq!(eval("2^3^2"), 512.0);
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("2 - 3 - 4"), -5.0);
+        assert_eq!(eval("p * 4"), 1.0);
+        assert_eq!(eval("mix(1, 3, 0.5)"), 2.0);
+    }
+
+    #[test]
+    fn functions_are_checked() {
+        let err = error("p + wobble(p)");
+        assert_eq!(err.message, "unknown function 'wobble'");
+        assert_eq!(err.position, 4);
+        assert_eq!(error("min(p)").message, "'min' takes 2 argument(s)");
+        assert_eq!(eval("sine_in_out(p)"), Signal::SINE_IN_OUT.amp(0.25));
+    }
+
+    #[test]
+    fn errors_are_positioned_by_character() {
+        // 'é' is two bytes, but a single column.
+        let err = error("é + $");
+        assert_eq!(err.position, 4);
+        assert_eq!(err.to_string(), "unexpected character '$' at column 5");
+        // Running out of input is reported just past the end.
+        assert_eq!(error("sin(p").position, 5);
+        assert_eq!(error("1 2").position, 2);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        assert_eq!(eval(&format!("{}1{}", "(".repeat(20), ")".repeat(20))), 1.0);
+        let deep = [
+            format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("{}1", "-".repeat(100_000)),
+            format!("{}1", "2^".repeat(100_000)),
+            format!("{}p", "p + ".repeat(100_000)),
+            format!("{}p{}", "sin(".repeat(100_000), ")".repeat(100_000)),
+        ];
+        for source in &deep {
+            assert_eq!(error(source).message, "formula nests too deeply");
+        }
+    }
+
+    #[test]
+    fn serde_round_trips_through_the_source() {
+        let formula = Formula::parse("sin(p * 2) * 0.5 + tri(p + 0.25) * 0.5").unwrap();
+        let json = serde_json::to_string(&formula).unwrap();
+        assert_eq!(json, "\"sin(p * 2) * 0.5 + tri(p + 0.25) * 0.5\"");
+        assert_eq!(serde_json::from_str::<Formula>(&json).unwrap(), formula);
+        let deep = format!("\"{}\"", "(".repeat(100_000));
+        assert!(serde_json::from_str::<Formula>(&deep).is_err());
+    }
+}
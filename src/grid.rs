@@ -0,0 +1,91 @@
+// Oscillator grid module
+//
+// Lays the shm oscillators out in rows for LED panels and matrices, instead of along a single
+// strip. The phase offset of each cell comes from its column and row, or from its distance or
+// angle around a centre point.
+use std::f32::consts::PI;
+
+/// Each of the offset modes, in the order they are listed in the GUI.
+pub const ALL: &[Offset] = &[Offset::Linear, Offset::Radial, Offset::Angular];
+
+/// The default phase offset between neighbouring columns.
+pub const DEFAULT_X_VELOCITY: f32 = 0.005;
+/// The default phase offset between neighbouring rows.
+pub const DEFAULT_Y_VELOCITY: f32 = 0.05;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Offset {
+    // Offset by `x_velocity` per column and `y_velocity` per row
+    Linear,
+    // Offset by the shm `a_velocity` per cell of distance from the centre, giving rings
+    Radial,
+    // Offset by the angle around the centre, `arms` whole cycles per turn, giving spokes
+    Angular,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub offset: Offset,
+    pub x_velocity: f32,
+    pub y_velocity: f32,
+    // The point the radial and angular offsets are measured from, 0..1 across and down the grid
+    pub centre: [f32; 2],
+    // The number of cycles once around the centre in the angular mode
+    pub arms: u32,
+}
+
+impl Offset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Offset::Linear => "Linear",
+            Offset::Radial => "Radial",
+            Offset::Angular => "Angular",
+        }
+    }
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Grid {
+            width,
+            height,
+            offset: Offset::Linear,
+            x_velocity: DEFAULT_X_VELOCITY,
+            y_velocity: DEFAULT_Y_VELOCITY,
+            centre: [0.5, 0.5],
+            arms: 1,
+        }
+    }
+
+    /// The number of cells, one oscillator each
+    pub fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// The column and row of the cell at the given index, counting along the rows
+    pub fn cell(&self, i: usize) -> (usize, usize) {
+        let width = self.width.max(1);
+        (i % width, i / width)
+    }
+
+    /// The phase offset of the cell at the given column and row
+    pub fn offset(&self, x: usize, y: usize, a_velocity: f32) -> f32 {
+        let cx = self.centre[0] * (self.width.max(1) - 1) as f32;
+        let cy = self.centre[1] * (self.height.max(1) - 1) as f32;
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        match self.offset {
+            Offset::Linear => x as f32 * self.x_velocity + y as f32 * self.y_velocity,
+            Offset::Radial => (dx * dx + dy * dy).sqrt() * a_velocity,
+            Offset::Angular => (dy.atan2(dx) / (2.0 * PI) + 0.5) * self.arms as f32,
+        }
+    }
+
+    /// Read the phase of the cell under the given point, 0..1 across and down the grid.
+    pub fn sample(&self, phases: &[f32], x: f32, y: f32) -> f32 {
+        let col = ((x * self.width as f32) as usize).min(self.width.max(1) - 1);
+        let row = ((y * self.height as f32) as usize).min(self.height.max(1) - 1);
+        phases.get(row * self.width + col).cloned().unwrap_or(0.0)
+    }
+}
@@ -1,6 +1,19 @@
-use super::SignalParams;
-use crate::shm::Shm;
-use crate::TOTAL_LED_PIXELS;
+use super::{SignalParams, Source};
+use crate::chaos;
+use crate::envelope::Shape;
+use crate::formula::Formula;
+use crate::grid::{self, Grid};
+use crate::layer::{self, Layers};
+use crate::names;
+use crate::preset::Preset;
+use crate::rhythm::{self, Rhythm};
+use crate::shaper::{self, Shaper};
+use crate::signals::{Combine, EasingType, LfoType, Signal};
+use crate::spread::{self, Distribution};
+use crate::symmetry::Direction;
+use crate::tempo;
+use crate::wavetable;
+use crate::{NUM_LED_STRIPS, PIXELS_PER_LED_STRIP, TOTAL_LED_PIXELS};
 use nannou::ui::conrod_core::widget_ids;
 use nannou::ui::prelude::*;
 use nannou::ui::Color;
@@ -13,21 +26,136 @@ pub const DEFAULT_SLIDER_H: Scalar = 20.0;
 pub const HALF_WIDGET_W: Scalar = WIDGET_W * 0.5 - PAD * 0.25;
 pub const THIRD_WIDGET_W: Scalar = WIDGET_W * 0.33 - PAD * 0.25;
 pub const WIDGET_COLOUR: Color = Color::Rgba(0.98, 0.1, 0.28, 1.0);
+// The seconds of transport time the scrub slider spans
+const SCRUB_WINDOW: f64 = 60.0;
 
 widget_ids! {
     pub struct Ids {
         background,
         title_text,
-        min_max,
+        layer_select[],
+        add_layer,
+        remove_layer,
+        layer_blend,
+        layer_opacity,
+        grid_background,
+        grid_title_text,
+        grid_on,
+        grid_width,
+        grid_height,
+        grid_offset,
+        grid_x_velocity,
+        grid_y_velocity,
+        grid_arms,
+        grid_centre,
+        spread_background,
+        spread_title_text,
+        spread_distribution,
+        spread_speed,
+        spread_phase,
+        spread_seed,
+        spread_p1,
+        spread_p2,
+        transport_background,
+        transport_title_text,
+        play,
+        reverse,
+        scrub,
+        seek,
         speed,
         offset,
         offset_hz,
-        pow,
         skew,
         count,
-        mirror,
-        invert,
+        mirror_segments,
+        mirror_rotation,
+        mirror_reflect,
+        mirror_centre_out,
+        mirror_reverse,
+        formula,
+        formula_error,
         signal_type,
+        pulse_width,
+        overshoot,
+        amplitude,
+        period,
+        exponent,
+        seed,
+        steps,
+        position,
+        cubic,
+        mix,
+        depth,
+        bezier_text,
+        bezier_error,
+        bezier_p1,
+        bezier_p2,
+        pulses,
+        rhythm_steps,
+        rotation,
+        gate,
+        sequencer_len,
+        step_levels[],
+        step_glides[],
+        morph_background,
+        morph_title_text,
+        morph_on,
+        morph_signal,
+        morph,
+        morph_lfo_on,
+        morph_lfo_hz,
+        transition_time,
+        analysis_background,
+        analysis_title_text,
+        analysis_text,
+        normalise,
+        shapers_background,
+        shapers_title_text,
+        stage_bypass[],
+        stage_param[],
+        stage_up[],
+        stage_remove[],
+        add_shaper,
+        save_preset,
+        load_preset,
+        spring_background,
+        spring_title_text,
+        spring_on,
+        stiffness,
+        damping,
+        mass,
+        spring_hold,
+        spring_target,
+        spring_kick,
+        envelope_background,
+        envelope_title_text,
+        envelope_on,
+        envelope_shape,
+        envelope_attack,
+        envelope_decay,
+        envelope_sustain,
+        envelope_gate,
+        envelope_release,
+        envelope_hold,
+        envelope_duration,
+        envelope_trigger,
+        chaos_background,
+        chaos_title_text,
+        chaos_on,
+        chaos_system,
+        chaos_rate,
+        chaos_spread,
+        tempo_background,
+        tempo_title_text,
+        bpm,
+        tap,
+        resync,
+        nudge_back,
+        nudge_forward,
+        beat_text,
+        tempo_sync,
+        division,
+        downbeat_reset,
         dmx_on,
         laser_on,
         audio_on,
@@ -36,7 +164,7 @@ widget_ids! {
 }
 
 /// Update the user interface.
-pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, shm: &mut Shm) {
+pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, layers: &mut Layers) {
     widget::Canvas::new()
         .pad(PAD)
         .border(0.0)
@@ -86,26 +214,107 @@ pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, shm:
         params.hue = value;
     }
 
-    let min = -1.0;
-    let max = 1.0;
-    for (edge, value) in widget::RangeSlider::new(params.min, params.max, min, max)
-        .color(WIDGET_COLOUR)
-        .label("Amplitude")
-        .down(10.0)
-        .label_font_size(14)
-        .label_rgb(1.0, 1.0, 1.0)
-        .w_h(200.0, 20.0)
-        .set(ids.min_max, ui)
-    {
-        match edge {
-            widget::range_slider::Edge::Start => params.min = value,
-            widget::range_slider::Edge::End => params.max = value,
+    // A button to select each layer for editing with the controls below, followed by buttons
+    // to add and remove layers.
+    let num_layers = layers.count();
+    let mut id_gen = ui.widget_id_generator();
+    ids.layer_select.resize(num_layers, &mut id_gen);
+    let gap = PAD * 0.2;
+    let layer_w =
+        (WIDGET_W - gap * layer::MAX_LAYERS as Scalar) / (layer::MAX_LAYERS + 1) as Scalar;
+    let mut selected = None;
+    for i in 0..num_layers {
+        let label = format!("{}", i + 1);
+        let select = toggle(i == layers.selected_idx())
+            .w_h(layer_w, DEFAULT_WIDGET_H)
+            .label(&label);
+        let select = match i {
+            0 => select.mid_left_of(ids.background).down(10.0),
+            _ => select.right_from(ids.layer_select[i - 1], gap),
+        };
+        for _value in select.set(ids.layer_select[i], ui) {
+            selected = Some(i);
         }
     }
+    if let Some(i) = selected {
+        layers.select(i);
+    }
 
-    for value in slider(shm.hz, -1.0, 1.0)
+    if num_layers < layer::MAX_LAYERS {
+        for _click in button("+")
+            .w_h(layer_w * 0.5 - gap * 0.5, DEFAULT_WIDGET_H)
+            .right_from(ids.layer_select[num_layers - 1], gap)
+            .set(ids.add_layer, ui)
+        {
+            layers.push(Signal::SINE);
+            selected = Some(layers.selected_idx());
+        }
+    }
+
+    if num_layers > 1 {
+        let remove = button("-").w_h(layer_w * 0.5 - gap * 0.5, DEFAULT_WIDGET_H);
+        let remove = match num_layers < layer::MAX_LAYERS {
+            true => remove.right_from(ids.add_layer, gap),
+            false => remove.right_from(ids.layer_select[num_layers - 1], gap),
+        };
+        for _click in remove.set(ids.remove_layer, ui) {
+            layers.remove_selected();
+            selected = Some(layers.selected_idx());
+        }
+    }
+
+    // Show the signals of the newly selected layer in the signal lists.
+    if selected.is_some() {
+        let shm = &layers.selected().shm;
+        let signals = &params.signals;
+        let selected_idx = signals.iter().position(|s| s == shm.signal_type());
+        let morph_idx = shm
+            .morph_signal()
+            .and_then(|morph| signals.iter().position(|s| s == morph));
+        params.selected_idx = selected_idx;
+        params.morph_idx = morph_idx;
+    }
+
+    // The layers above the bottom one blend onto those beneath them.
+    if layers.selected_idx() > 0 {
+        let layer = layers.selected_mut();
+        let blend_names: Vec<&str> = layer::ALL.iter().map(|b| b.name()).collect();
+        let blend_idx = layer::ALL.iter().position(|&b| b == layer.blend);
+        for blend_idx in widget::DropDownList::new(&blend_names, blend_idx)
+            .w_h(HALF_WIDGET_W, DEFAULT_SLIDER_H)
+            .down_from(ids.layer_select[0], 10.0)
+            .align_left_of(ids.layer_select[0])
+            .max_visible_items(6)
+            .color(WIDGET_COLOUR)
+            .label_font_size(12)
+            .label_rgb(1.0, 1.0, 1.0)
+            .scrollbar_on_top()
+            .set(ids.layer_blend, ui)
+        {
+            layer.blend = layer::ALL[blend_idx];
+        }
+
+        for value in slider(layer.opacity, 0.0, 1.0)
+            .w(HALF_WIDGET_W)
+            .right(PAD * 0.5)
+            .label("Opacity")
+            .set(ids.layer_opacity, ui)
+        {
+            layer.opacity = value;
+        }
+    }
+
+    // The rest of the panel edits the selected layer.
+    let layer = layers.selected_mut();
+    let shm = &mut layer.shm;
+    let mut size = None;
+
+    // The tempo clock sets the speed of synced layers.
+    for value in slider(shm.hz, -3.0, 3.0)
         .down(10.0)
+        .align_left_of(ids.hue)
         .label("Speed")
+        .enabled(!layer.tempo_sync)
         .set(ids.speed, ui)
     {
         shm.hz = value;
@@ -119,7 +328,9 @@ pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, shm:
         shm.a_velocity = value;
     }
 
-    for value in slider(shm.offset_hz, 0.0, 1.0)
+    // The drift between neighbours in cycles per second, whatever the count. Even a tenth of a
+    // cycle runs through the strip's whole pattern in seconds.
+    for value in slider(shm.offset_hz, 0.0, 0.1)
         .down(10.0)
         .label("Offset Hz")
         .set(ids.offset_hz, ui)
@@ -127,14 +338,6 @@ pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, shm:
         shm.offset_hz = value;
     }
 
-    for value in slider(params.pow, 0.5, 10.0)
-        .down(10.0)
-        .label("Pow")
-        .set(ids.pow, ui)
-    {
-        params.pow = value;
-    }
-
     for value in slider(shm.skew, 0.25, 10.0)
         .down(10.0)
         .label("Skew")
@@ -143,37 +346,111 @@ pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, shm:
         shm.skew = value;
     }
 
+    // The grid sets the count when the oscillators are laid out in one.
     for value in slider(shm.size() as f32, 1.0, TOTAL_LED_PIXELS as f32)
         .down(10.0)
+        .enabled(shm.grid().is_none())
         .label("Count")
         .set(ids.count, ui)
     {
-        shm.set_size(value as _);
+        size = Some(value as usize);
 
         //m.stream.send(move |audio| { audio.oscillators.resize(value as usize, Oscillator{phase: 0.0, hz: 100.0}); }).unwrap();
     }
 
-    for value in toggle(shm.mirror)
+    // The symmetry of the pattern along the strip.
+    let symmetry = &mut shm.symmetry;
+    let segments_label = format!("Mirror x{}", symmetry.segments);
+    for value in slider(symmetry.segments as f32, 1.0, 16.0)
         .down(10.0)
         .w(HALF_WIDGET_W)
-        .label("Mirror")
-        .set(ids.mirror, ui)
+        .label(&segments_label)
+        .set(ids.mirror_segments, ui)
     {
-        shm.mirror = value;
+        symmetry.segments = value as u32;
     }
 
-    for value in toggle(params.invert)
-        .right(10.0)
+    for value in slider(symmetry.rotation, 0.0, 1.0)
+        .right(PAD * 0.5)
         .w(HALF_WIDGET_W)
-        .label("Invert")
-        .set(ids.invert, ui)
+        .label("Rotation")
+        .set(ids.mirror_rotation, ui)
     {
-        params.invert = value;
+        symmetry.rotation = value;
+    }
+
+    // Reflecting and the direction within segments only make a difference with two or more.
+    let segmented = symmetry.segments > 1;
+    for value in toggle(symmetry.reflect)
+        .down_from(ids.mirror_segments, 10.0)
+        .align_left_of(ids.mirror_segments)
+        .w(THIRD_WIDGET_W)
+        .label("Reflect")
+        .label_font_size(10)
+        .enabled(segmented)
+        .set(ids.mirror_reflect, ui)
+    {
+        symmetry.reflect = value;
+    }
+
+    for value in toggle(symmetry.direction == Direction::CentreOut)
+        .right(PAD * 0.4)
+        .w(THIRD_WIDGET_W)
+        .label("Centre Out")
+        .label_font_size(10)
+        .enabled(segmented)
+        .set(ids.mirror_centre_out, ui)
+    {
+        symmetry.direction = match value {
+            true => Direction::CentreOut,
+            false => Direction::EdgesIn,
+        };
+    }
+
+    for value in toggle(symmetry.reverse)
+        .right(PAD * 0.4)
+        .w(THIRD_WIDGET_W)
+        .label("Reverse")
+        .label_font_size(10)
+        .set(ids.mirror_reverse, ui)
+    {
+        symmetry.reverse = value;
+    }
+
+    for event in widget::TextBox::new(&params.formula)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down_from(ids.mirror_reflect, 10.0)
+        .align_left_of(ids.mirror_reflect)
+        .font_size(14)
+        .left_justify()
+        .set(ids.formula, ui)
+    {
+        match event {
+            widget::text_box::Event::Update(text) => params.formula = text,
+            widget::text_box::Event::Enter => match Formula::parse(&params.formula) {
+                Ok(formula) => {
+                    params.selected_idx = None;
+                    params.formula_error = None;
+                    shm.transition_to(Signal::Formula(formula));
+                }
+                Err(err) => params.formula_error = Some(err.to_string()),
+            },
+        }
+    }
+
+    // Report the formula's parse error at the bottom of the panel.
+    if let Some(ref err) = params.formula_error {
+        text(err)
+            .w(WIDGET_W)
+            .font_size(12)
+            .wrap_by_word()
+            .mid_bottom_of(ids.background)
+            .set(ids.formula_error, ui);
     }
 
     for selected_idx in widget::DropDownList::new(&params.signal_names, params.selected_idx)
         .w_h(WIDGET_W, DEFAULT_WIDGET_H)
-        .down_from(ids.mirror, 10.0)
+        .down_from(ids.formula, 10.0)
         .max_visible_items(10)
         .color(WIDGET_COLOUR)
         .label("Signal Type")
@@ -183,7 +460,1273 @@ pub fn update(ref mut ui: UiCell, ids: &mut Ids, params: &mut SignalParams, shm:
         .set(ids.signal_type, ui)
     {
         params.selected_idx = Some(selected_idx);
+        shm.transition_to(params.signals[selected_idx].clone());
+    }
+
+    // Show the parameters of the selected signal, if it has any.
+    let mut signal = shm.signal_type().clone();
+    match &mut signal {
+        Signal::Lfo(LfoType::Square { pulse_width }) => {
+            for value in slider(*pulse_width, 0.0, 1.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Pulse Width")
+                .set(ids.pulse_width, ui)
+            {
+                *pulse_width = value;
+            }
+        }
+        Signal::Lfo(LfoType::ValueNoise { seed, steps })
+        | Signal::Lfo(LfoType::PerlinNoise { seed, steps })
+        | Signal::Lfo(LfoType::SampleAndHold { seed, steps })
+        | Signal::Lfo(LfoType::RandomWalk { seed, steps }) => {
+            for value in slider(*seed as f32, 0.0, 1000.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Seed")
+                .set(ids.seed, ui)
+            {
+                *seed = value as _;
+            }
+
+            for value in slider(*steps as f32, 1.0, 64.0)
+                .down(10.0)
+                .label("Steps")
+                .set(ids.steps, ui)
+            {
+                *steps = value as _;
+            }
+        }
+        Signal::Ease(EasingType::BackIn { overshoot })
+        | Signal::Ease(EasingType::BackInOut { overshoot })
+        | Signal::Ease(EasingType::BackInOutS { overshoot })
+        | Signal::Ease(EasingType::BackInS { overshoot })
+        | Signal::Ease(EasingType::BackOut { overshoot })
+        | Signal::Ease(EasingType::BackOutS { overshoot }) => {
+            for value in slider(*overshoot, 0.0, 4.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Overshoot")
+                .set(ids.overshoot, ui)
+            {
+                *overshoot = value;
+            }
+        }
+        Signal::Ease(EasingType::ElasticIn { amplitude, period })
+        | Signal::Ease(EasingType::ElasticInOut { amplitude, period })
+        | Signal::Ease(EasingType::ElasticOut { amplitude, period }) => {
+            for value in slider(*amplitude, 1.0, 4.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Amplitude")
+                .set(ids.amplitude, ui)
+            {
+                *amplitude = value;
+            }
+
+            for value in slider(*period, 0.05, 1.0)
+                .down(10.0)
+                .label("Period")
+                .set(ids.period, ui)
+            {
+                *period = value;
+            }
+        }
+        Signal::Ease(EasingType::PowerIn { exponent })
+        | Signal::Ease(EasingType::PowerInOut { exponent })
+        | Signal::Ease(EasingType::PowerOut { exponent }) => {
+            for value in slider(*exponent, 0.25, 8.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Exponent")
+                .set(ids.exponent, ui)
+            {
+                *exponent = value;
+            }
+        }
+        Signal::Ease(EasingType::CubicBezier { x1, y1, x2, y2 }) => {
+            for event in widget::TextBox::new(&params.bezier)
+                .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+                .down_from(ids.signal_type, 10.0)
+                .font_size(14)
+                .left_justify()
+                .set(ids.bezier_text, ui)
+            {
+                match event {
+                    widget::text_box::Event::Update(text) => params.bezier = text,
+                    widget::text_box::Event::Enter => match names::parse_bezier(&params.bezier) {
+                        Some(EasingType::CubicBezier {
+                            x1: a,
+                            y1: b,
+                            x2: c,
+                            y2: d,
+                        }) => {
+                            *x1 = a;
+                            *y1 = b;
+                            *x2 = c;
+                            *y2 = d;
+                            params.bezier_error = None;
+                        }
+                        _ => {
+                            let err = "expected four numbers such as 0.25, 0.1, 0.25, 1";
+                            params.bezier_error = Some(err.to_string());
+                        }
+                    },
+                }
+            }
+
+            // A handle for each control point. The y values may leave 0..1 to overshoot.
+            let mut moved = false;
+            for (x, y) in bezier_pad(*x1, *y1)
+                .down(10.0)
+                .label("P1")
+                .set(ids.bezier_p1, ui)
+            {
+                *x1 = x;
+                *y1 = y;
+                moved = true;
+            }
+
+            for (x, y) in bezier_pad(*x2, *y2)
+                .right(PAD * 0.5)
+                .label("P2")
+                .set(ids.bezier_p2, ui)
+            {
+                *x2 = x;
+                *y2 = y;
+                moved = true;
+            }
+
+            if moved {
+                params.bezier = format!("{:.2}, {:.2}, {:.2}, {:.2}", x1, y1, x2, y2);
+                params.bezier_error = None;
+            }
+
+            // Report the pasted handles' parse error beneath the handles.
+            if let Some(ref err) = params.bezier_error {
+                text(err)
+                    .w(WIDGET_W)
+                    .font_size(12)
+                    .wrap_by_word()
+                    .down_from(ids.bezier_p1, 10.0)
+                    .align_left_of(ids.bezier_p1)
+                    .set(ids.bezier_error, ui);
+            }
+        }
+        Signal::Rhythm(Rhythm::Euclidean {
+            pulses,
+            steps,
+            rotation,
+            gate,
+        }) => {
+            for value in slider(*steps as f32, 1.0, 32.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Steps")
+                .set(ids.rhythm_steps, ui)
+            {
+                *steps = value as _;
+            }
+
+            for value in slider(*pulses as f32, 0.0, *steps as f32)
+                .down(10.0)
+                .label("Pulses")
+                .set(ids.pulses, ui)
+            {
+                *pulses = value as _;
+            }
+
+            for value in slider(*rotation as f32, 0.0, *steps as f32 - 1.0)
+                .down(10.0)
+                .label("Rotation")
+                .set(ids.rotation, ui)
+            {
+                *rotation = value as _;
+            }
+
+            for value in slider(*gate, 0.05, 1.0)
+                .down(10.0)
+                .label("Gate")
+                .set(ids.gate, ui)
+            {
+                *gate = value;
+            }
+        }
+        Signal::Rhythm(Rhythm::Sequencer(sequencer)) => {
+            let long = sequencer.steps.len() > rhythm::SEQUENCER_LENGTHS[0];
+            for value in toggle(long)
+                .down_from(ids.signal_type, 10.0)
+                .w(WIDGET_W)
+                .label("32 Steps")
+                .set(ids.sequencer_len, ui)
+            {
+                let len = rhythm::SEQUENCER_LENGTHS[value as usize];
+                sequencer.set_len(len);
+            }
+
+            let num_steps = sequencer.steps.len();
+            {
+                let mut id_gen = ui.widget_id_generator();
+                ids.step_levels.resize(num_steps, &mut id_gen);
+                ids.step_glides.resize(num_steps, &mut id_gen);
+            }
+
+            // A vertical slider for the level of each step with its glide toggle beneath,
+            // in rows of 16.
+            let row_len = rhythm::SEQUENCER_LENGTHS[0];
+            let gap = 2.0;
+            let step_w = (WIDGET_W - gap * (row_len - 1) as Scalar) / row_len as Scalar;
+            for i in 0..num_steps {
+                let step = sequencer.steps[i];
+                let level = slider(step.level, 0.0, 1.0).w_h(step_w, 50.0);
+                let level = match i {
+                    0 => level
+                        .down_from(ids.sequencer_len, 10.0)
+                        .align_left_of(ids.sequencer_len),
+                    i if i % row_len == 0 => level
+                        .down_from(ids.step_glides[i - row_len], 10.0)
+                        .align_left_of(ids.step_glides[i - row_len]),
+                    _ => level.right_from(ids.step_levels[i - 1], gap),
+                };
+                for value in level.set(ids.step_levels[i], ui) {
+                    sequencer.steps.to_mut()[i].level = value;
+                }
+
+                for value in toggle(step.glide)
+                    .w_h(step_w, step_w)
+                    .down_from(ids.step_levels[i], gap)
+                    .set(ids.step_glides[i], ui)
+                {
+                    sequencer.steps.to_mut()[i].glide = value;
+                }
+            }
+        }
+        Signal::Wavetable(table) => {
+            let cubic = table.interpolation == wavetable::Interpolation::Cubic;
+            for value in toggle(cubic)
+                .down_from(ids.signal_type, 10.0)
+                .w(WIDGET_W)
+                .label("Cubic")
+                .set(ids.cubic, ui)
+            {
+                table.interpolation = match value {
+                    true => wavetable::Interpolation::Cubic,
+                    false => wavetable::Interpolation::Linear,
+                };
+            }
+
+            if table.num_frames() > 1 {
+                for value in slider(table.position, 0.0, 1.0)
+                    .down(10.0)
+                    .label("Position")
+                    .set(ids.position, ui)
+                {
+                    table.position = value;
+                }
+            }
+        }
+        Signal::Combine(Combine::Crossfade { mix, .. }) => {
+            for value in slider(*mix, 0.0, 1.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Mix")
+                .set(ids.mix, ui)
+            {
+                *mix = value;
+            }
+        }
+        Signal::Combine(Combine::PhaseMod { depth, .. }) => {
+            for value in slider(*depth, 0.0, 1.0)
+                .down_from(ids.signal_type, 10.0)
+                .label("Depth")
+                .set(ids.depth, ui)
+            {
+                *depth = value;
+            }
+        }
+        _ => (),
     }
+    shm.set_signal_type(signal);
+
+    // Morphing and crossfade controls live in their own panel on the right.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 300.0)
+        .top_right()
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.morph_background, ui);
+
+    text("MORPH")
+        .mid_top_of(ids.morph_background)
+        .set(ids.morph_title_text, ui);
+
+    for value in toggle(shm.morph_signal().is_some())
+        .mid_left_of(ids.morph_background)
+        .down(20.0)
+        .w(WIDGET_W)
+        .label("Morph")
+        .set(ids.morph_on, ui)
+    {
+        let morph_signal = match value {
+            true => Some(params.signals[params.morph_idx.unwrap_or(0)].clone()),
+            false => None,
+        };
+        shm.set_morph_signal(morph_signal);
+    }
+
+    // The morph lfo sets the amount while it's on.
+    for value in slider(shm.morph_amount(), 0.0, 1.0)
+        .down(10.0)
+        .enabled(shm.morph_lfo.is_none())
+        .label("Morph Amount")
+        .set(ids.morph, ui)
+    {
+        shm.morph = value;
+    }
+
+    for value in toggle(shm.morph_lfo.is_some())
+        .down(10.0)
+        .w(WIDGET_W)
+        .label("Morph LFO")
+        .set(ids.morph_lfo_on, ui)
+    {
+        shm.morph_lfo = match value {
+            true => Some(LfoType::Sine),
+            false => None,
+        };
+    }
+
+    for value in slider(shm.morph_lfo_hz, 0.0, 3.0)
+        .down(10.0)
+        .label("Morph Hz")
+        .set(ids.morph_lfo_hz, ui)
+    {
+        shm.morph_lfo_hz = value;
+    }
+
+    for value in slider(shm.transition_time, 0.0, 10.0)
+        .down(10.0)
+        .label("Crossfade Time")
+        .set(ids.transition_time, ui)
+    {
+        shm.transition_time = value;
+    }
+
+    for morph_idx in widget::DropDownList::new(&params.signal_names, params.morph_idx)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .max_visible_items(10)
+        .color(WIDGET_COLOUR)
+        .label("Morph To")
+        .label_font_size(14)
+        .label_rgb(1.0, 1.0, 1.0)
+        .scrollbar_on_top()
+        .set(ids.morph_signal, ui)
+    {
+        params.morph_idx = Some(morph_idx);
+        shm.set_morph_signal(Some(params.signals[morph_idx].clone()));
+    }
+
+    // A readout of the analysis of the bottom layer's signal, which the audio also plays.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 220.0)
+        .bottom_right()
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.analysis_background, ui);
+
+    text("ANALYSIS (LAYER 1)")
+        .mid_top_of(ids.analysis_background)
+        .set(ids.analysis_title_text, ui);
+
+    let analysis = &params.analysis;
+    let harmonics: Vec<String> = analysis
+        .harmonics
+        .iter()
+        .take(4)
+        .map(|h| format!("{:.2}", h))
+        .collect();
+    let readout = format!(
+        "Min {:.2}   Max {:.2}\nMean {:.2}   RMS {:.2}\nOvershoot {:.2}\nDiscontinuities {}\nHarmonics {}",
+        analysis.min,
+        analysis.max,
+        analysis.mean,
+        analysis.rms,
+        analysis.overshoot,
+        analysis.discontinuities.len(),
+        harmonics.join(" "),
+    );
+    text(&readout)
+        .font_size(12)
+        .w(WIDGET_W)
+        .mid_left_of(ids.analysis_background)
+        .down(20.0)
+        .set(ids.analysis_text, ui);
+
+    for value in toggle(params.normalise)
+        .down(10.0)
+        .w(WIDGET_W)
+        .label("Normalise")
+        .set(ids.normalise, ui)
+    {
+        params.normalise = value;
+    }
+
+    // The damped springs share the bottom of the window with the analysis.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 270.0)
+        .left_from(ids.analysis_background, 0.0)
+        .align_bottom_of(ids.analysis_background)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.spring_background, ui);
+
+    text("SPRING")
+        .mid_top_of(ids.spring_background)
+        .set(ids.spring_title_text, ui);
+
+    for value in toggle(params.spring_on)
+        .mid_left_of(ids.spring_background)
+        .down(20.0)
+        .w(WIDGET_W)
+        .label("Spring")
+        .set(ids.spring_on, ui)
+    {
+        params.spring_on = value;
+    }
+
+    let spring = &mut params.spring;
+    for value in slider(spring.stiffness, 1.0, 500.0)
+        .down(10.0)
+        .label("Stiffness")
+        .set(ids.stiffness, ui)
+    {
+        spring.stiffness = value;
+    }
+
+    for value in slider(spring.damping, 0.0, 40.0)
+        .down(10.0)
+        .label("Damping")
+        .set(ids.damping, ui)
+    {
+        spring.damping = value;
+    }
+
+    for value in slider(spring.mass, 0.1, 10.0)
+        .down(10.0)
+        .label("Mass")
+        .set(ids.mass, ui)
+    {
+        spring.mass = value;
+    }
+
+    for value in toggle(spring.target.is_some())
+        .down(10.0)
+        .w(WIDGET_W)
+        .label("Hold Target")
+        .set(ids.spring_hold, ui)
+    {
+        spring.target = match value {
+            true => Some(0.0),
+            false => None,
+        };
+    }
+
+    if let Some(ref mut target) = spring.target {
+        for value in slider(*target, -1.0, 1.0)
+            .down(10.0)
+            .label("Target")
+            .set(ids.spring_target, ui)
+        {
+            *target = value;
+        }
+    }
+
+    for _click in button("Kick")
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .set(ids.spring_kick, ui)
+    {
+        spring.trigger(8.0);
+    }
+
+    // The envelope panel sits beside the morph panel.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 330.0)
+        .left_from(ids.morph_background, 0.0)
+        .align_top_of(ids.morph_background)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.envelope_background, ui);
+
+    text("ENVELOPE")
+        .mid_top_of(ids.envelope_background)
+        .set(ids.envelope_title_text, ui);
+
+    for value in toggle(params.source == Source::Envelope)
+        .mid_left_of(ids.envelope_background)
+        .down(20.0)
+        .w(WIDGET_W)
+        .label("Envelope")
+        .set(ids.envelope_on, ui)
+    {
+        params.source = match value {
+            true => Source::Envelope,
+            false => Source::Shm,
+        };
+    }
+
+    for _click in button("Trigger")
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .set(ids.envelope_trigger, ui)
+    {
+        params.envelope.trigger();
+    }
+
+    for envelope_idx in widget::DropDownList::new(&params.envelope_names, params.envelope_idx)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .max_visible_items(8)
+        .color(WIDGET_COLOUR)
+        .label("Envelope Shape")
+        .label_font_size(14)
+        .label_rgb(1.0, 1.0, 1.0)
+        .scrollbar_on_top()
+        .set(ids.envelope_shape, ui)
+    {
+        params.envelope_idx = Some(envelope_idx);
+        params.envelope.shape = params.envelope_shapes[envelope_idx];
+    }
+
+    match &mut params.envelope.shape {
+        Shape::Adsr {
+            attack,
+            decay,
+            sustain,
+            gate,
+            release,
+        } => {
+            for value in slider(*attack, 0.0, 2.0)
+                .down_from(ids.envelope_shape, 10.0)
+                .label("Attack")
+                .set(ids.envelope_attack, ui)
+            {
+                *attack = value;
+            }
+
+            for value in slider(*decay, 0.0, 2.0)
+                .down(10.0)
+                .label("Decay")
+                .set(ids.envelope_decay, ui)
+            {
+                *decay = value;
+            }
+
+            for value in slider(*sustain, 0.0, 1.0)
+                .down(10.0)
+                .label("Sustain")
+                .set(ids.envelope_sustain, ui)
+            {
+                *sustain = value;
+            }
+
+            for value in slider(*gate, 0.0, 4.0)
+                .down(10.0)
+                .label("Gate")
+                .set(ids.envelope_gate, ui)
+            {
+                *gate = value;
+            }
+
+            for value in slider(*release, 0.0, 4.0)
+                .down(10.0)
+                .label("Release")
+                .set(ids.envelope_release, ui)
+            {
+                *release = value;
+            }
+        }
+        Shape::Ahd {
+            attack,
+            hold,
+            decay,
+        } => {
+            for value in slider(*attack, 0.0, 2.0)
+                .down_from(ids.envelope_shape, 10.0)
+                .label("Attack")
+                .set(ids.envelope_attack, ui)
+            {
+                *attack = value;
+            }
+
+            for value in slider(*hold, 0.0, 2.0)
+                .down(10.0)
+                .label("Hold")
+                .set(ids.envelope_hold, ui)
+            {
+                *hold = value;
+            }
+
+            for value in slider(*decay, 0.0, 4.0)
+                .down(10.0)
+                .label("Decay")
+                .set(ids.envelope_decay, ui)
+            {
+                *decay = value;
+            }
+        }
+        Shape::Ease { duration, .. } => {
+            for value in slider(*duration, 0.05, 4.0)
+                .down_from(ids.envelope_shape, 10.0)
+                .label("Duration")
+                .set(ids.envelope_duration, ui)
+            {
+                *duration = value;
+            }
+        }
+    }
+
+    // The chaotic systems fill the gap between the morph and analysis panels.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 230.0)
+        .down_from(ids.morph_background, 0.0)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.chaos_background, ui);
+
+    text("CHAOS")
+        .mid_top_of(ids.chaos_background)
+        .set(ids.chaos_title_text, ui);
+
+    for value in toggle(params.source == Source::Chaos)
+        .mid_left_of(ids.chaos_background)
+        .down(20.0)
+        .w(WIDGET_W)
+        .label("Chaos")
+        .set(ids.chaos_on, ui)
+    {
+        params.source = match value {
+            true => Source::Chaos,
+            false => Source::Shm,
+        };
+    }
+
+    let system_names: Vec<&str> = chaos::ALL.iter().map(|s| s.name()).collect();
+    for chaos_idx in widget::DropDownList::new(&system_names, params.chaos_idx)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .color(WIDGET_COLOUR)
+        .label(params.chaos.system().name())
+        .label_font_size(14)
+        .label_rgb(1.0, 1.0, 1.0)
+        .scrollbar_on_top()
+        .set(ids.chaos_system, ui)
+    {
+        params.chaos_idx = Some(chaos_idx);
+        params.chaos.set_system(chaos::ALL[chaos_idx]);
+    }
+
+    for value in slider(params.chaos.rate, 0.0, 2.0)
+        .down(10.0)
+        .label("Rate")
+        .set(ids.chaos_rate, ui)
+    {
+        params.chaos.rate = value;
+    }
+
+    for value in slider(params.chaos.spread(), 0.0, 0.1)
+        .down(10.0)
+        .label("Spread")
+        .set(ids.chaos_spread, ui)
+    {
+        params.chaos.set_spread(value);
+    }
+
+    // The tempo clock sits at the bottom of the column beside the main panel.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 330.0)
+        .right_from(ids.background, 0.0)
+        .align_bottom_of(ids.background)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.tempo_background, ui);
+
+    text("TEMPO")
+        .mid_top_of(ids.tempo_background)
+        .set(ids.tempo_title_text, ui);
+
+    for value in slider(params.clock.bpm, tempo::MIN_BPM, tempo::MAX_BPM)
+        .mid_left_of(ids.tempo_background)
+        .down(20.0)
+        .label("BPM")
+        .set(ids.bpm, ui)
+    {
+        params.clock.bpm = value;
+    }
+
+    for _click in button("Tap")
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .set(ids.tap, ui)
+    {
+        params.clock.tap();
+    }
+
+    // Nudging or resyncing the clock lines the synced layers up with it once they're updated.
+    let mut resync = false;
+    for _click in button("Resync")
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .right(PAD * 0.5)
+        .set(ids.resync, ui)
+    {
+        params.clock.resync();
+        resync = true;
+    }
+
+    for _click in button("<< Nudge")
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .down_from(ids.tap, 10.0)
+        .set(ids.nudge_back, ui)
+    {
+        params.clock.nudge(-0.05);
+        resync = true;
+    }
+
+    for _click in button("Nudge >>")
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .right(PAD * 0.5)
+        .set(ids.nudge_forward, ui)
+    {
+        params.clock.nudge(0.05);
+        resync = true;
+    }
+
+    let (bar, beat) = params.clock.bar_and_beat();
+    let bpm = params.clock.bpm;
+    let beat_text = format!("{:.1} BPM   Bar {}  Beat {}", bpm, bar + 1, beat + 1);
+    text(&beat_text)
+        .font_size(12)
+        .down_from(ids.nudge_back, 10.0)
+        .set(ids.beat_text, ui);
+
+    // The speed of each layer is synced separately, so these follow the selected layer.
+    for value in toggle(layer.tempo_sync)
+        .down(10.0)
+        .w(WIDGET_W)
+        .label("Sync Layer Speed")
+        .set(ids.tempo_sync, ui)
+    {
+        layer.tempo_sync = value;
+    }
+
+    let division_names: Vec<&str> = tempo::DIVISIONS.iter().map(|d| d.name).collect();
+    for division_idx in widget::DropDownList::new(&division_names, Some(layer.division_idx))
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .max_visible_items(6)
+        .color(WIDGET_COLOUR)
+        .label("Division")
+        .label_font_size(14)
+        .label_rgb(1.0, 1.0, 1.0)
+        .scrollbar_on_top()
+        .set(ids.division, ui)
+    {
+        layer.division_idx = division_idx;
+    }
+
+    for value in toggle(params.downbeat_reset)
+        .down(10.0)
+        .w(WIDGET_W)
+        .label("Reset On Downbeat")
+        .set(ids.downbeat_reset, ui)
+    {
+        params.downbeat_reset = value;
+    }
+
+    // The shaper chain gets its own panel beside the main one, tall enough for every stage.
+    let num_stages = params.shapers.stages.len();
+    let row_h = DEFAULT_SLIDER_H + 10.0;
+    let shapers_h = PAD * 2.0 + 40.0 + (num_stages + 2) as Scalar * row_h + DEFAULT_WIDGET_H;
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, shapers_h)
+        .right_from(ids.background, 0.0)
+        .align_top_of(ids.background)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.shapers_background, ui);
+
+    text("SHAPERS")
+        .mid_top_of(ids.shapers_background)
+        .set(ids.shapers_title_text, ui);
+
+    {
+        let mut id_gen = ui.widget_id_generator();
+        ids.stage_bypass.resize(num_stages, &mut id_gen);
+        ids.stage_param.resize(num_stages, &mut id_gen);
+        ids.stage_up.resize(num_stages, &mut id_gen);
+        ids.stage_remove.resize(num_stages, &mut id_gen);
+    }
+
+    // Each stage is a row of its on/off toggle, its parameter and buttons to reorder or remove it.
+    let button_w = DEFAULT_SLIDER_H;
+    let param_w = WIDGET_W - THIRD_WIDGET_W - button_w * 2.0 - 15.0;
+    let mut move_up = None;
+    let mut remove = None;
+    for (i, stage) in params.shapers.stages.iter_mut().enumerate() {
+        let bypass = toggle(!stage.bypass)
+            .w_h(THIRD_WIDGET_W, DEFAULT_SLIDER_H)
+            .label(stage.shaper.name())
+            .label_font_size(10);
+        let bypass = match i {
+            0 => bypass.mid_left_of(ids.shapers_background).down(20.0),
+            _ => bypass
+                .down_from(ids.stage_bypass[i - 1], 10.0)
+                .align_left_of(ids.stage_bypass[i - 1]),
+        };
+        for value in bypass.set(ids.stage_bypass[i], ui) {
+            stage.bypass = !value;
+        }
+
+        let param = |value: f32, min: f32, max: f32| {
+            slider(value, min, max)
+                .w(param_w)
+                .right_from(ids.stage_bypass[i], 5.0)
+                .label_font_size(10)
+        };
+        let id = ids.stage_param[i];
+        match &mut stage.shaper {
+            Shaper::Fold { drive } | Shaper::SoftClip { drive } => {
+                for value in param(*drive, 0.1, 8.0).label("Drive").set(id, ui) {
+                    *drive = value;
+                }
+            }
+            Shaper::HardClip { threshold } => {
+                for value in param(*threshold, 0.0, 1.0).label("Threshold").set(id, ui) {
+                    *threshold = value;
+                }
+            }
+            Shaper::Quantise { steps } => {
+                for value in param(*steps as f32, 2.0, 32.0).label("Steps").set(id, ui) {
+                    *steps = value as _;
+                }
+            }
+            Shaper::Bias { amount } => {
+                for value in param(*amount, -1.0, 1.0).label("Amount").set(id, ui) {
+                    *amount = value;
+                }
+            }
+            Shaper::Gain { amount } => {
+                for value in param(*amount, 0.0, 4.0).label("Amount").set(id, ui) {
+                    *amount = value;
+                }
+            }
+            Shaper::Slew { rate } => {
+                for value in param(*rate, 0.05, 12.0).label("Rate").set(id, ui) {
+                    *rate = value;
+                }
+            }
+            Shaper::Pow { exponent } => {
+                for value in param(*exponent, 0.5, 10.0).label("Exponent").set(id, ui) {
+                    *exponent = value;
+                }
+            }
+            Shaper::Range { min, max } => {
+                for (edge, value) in widget::RangeSlider::new(*min, *max, -1.0, 1.0)
+                    .color(WIDGET_COLOUR)
+                    .label("Range")
+                    .label_font_size(10)
+                    .label_rgb(1.0, 1.0, 1.0)
+                    .w_h(param_w, DEFAULT_SLIDER_H)
+                    .right_from(ids.stage_bypass[i], 5.0)
+                    .set(id, ui)
+                {
+                    match edge {
+                        widget::range_slider::Edge::Start => *min = value,
+                        widget::range_slider::Edge::End => *max = value,
+                    }
+                }
+            }
+            Shaper::HalfRectify | Shaper::FullRectify | Shaper::Invert => (),
+        }
+
+        for _click in button("^")
+            .w_h(button_w, DEFAULT_SLIDER_H)
+            .right_from(ids.stage_bypass[i], param_w + 10.0)
+            .set(ids.stage_up[i], ui)
+        {
+            move_up = Some(i);
+        }
+
+        for _click in button("x")
+            .w_h(button_w, DEFAULT_SLIDER_H)
+            .right(5.0)
+            .set(ids.stage_remove[i], ui)
+        {
+            remove = Some(i);
+        }
+    }
+    if let Some(i) = move_up {
+        params.shapers.move_up(i);
+    }
+    if let Some(i) = remove {
+        params.shapers.remove(i);
+    }
+
+    let shaper_names: Vec<&str> = shaper::ALL.iter().map(|s| s.name()).collect();
+    let add_shaper = widget::DropDownList::new(&shaper_names, params.shaper_idx)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .max_visible_items(6)
+        .color(WIDGET_COLOUR)
+        .label("Add Shaper")
+        .label_font_size(14)
+        .label_rgb(1.0, 1.0, 1.0)
+        .scrollbar_on_top();
+    let add_shaper = match num_stages {
+        0 => add_shaper.mid_left_of(ids.shapers_background).down(20.0),
+        _ => add_shaper
+            .down_from(ids.stage_bypass[num_stages - 1], 10.0)
+            .align_left_of(ids.stage_bypass[num_stages - 1]),
+    };
+    for shaper_idx in add_shaper.set(ids.add_shaper, ui) {
+        params.shaper_idx = Some(shaper_idx);
+        params.shapers.push(shaper::ALL[shaper_idx]);
+    }
+
+    // Save or load the signal and shaper chain.
+    for _click in button("Save Preset")
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .set(ids.save_preset, ui)
+    {
+        if let Some(ref path) = params.preset_path {
+            let preset = Preset {
+                signal: shm.signal_type().clone(),
+                shapers: params.shapers.clone(),
+            };
+            if let Err(err) = preset.save(path) {
+                println!("Failed to save preset {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    for _click in button("Load Preset")
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .right(PAD * 0.5)
+        .set(ids.load_preset, ui)
+    {
+        if let Some(ref path) = params.preset_path {
+            match Preset::load(path) {
+                Ok(preset) => {
+                    params.selected_idx = None;
+                    params.shapers = preset.shapers;
+                    shm.transition_to(preset.signal);
+                }
+                Err(err) => println!("Failed to load preset {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    // The grid layout of the oscillators, collapsed to its toggle when they run along a strip.
+    let mut grid = shm.grid().cloned();
+    let grid_h = match grid {
+        Some(_) => 420.0,
+        None => 80.0,
+    };
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, grid_h)
+        .right_from(ids.shapers_background, 0.0)
+        .align_top_of(ids.background)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.grid_background, ui);
+
+    text("GRID")
+        .mid_top_of(ids.grid_background)
+        .set(ids.grid_title_text, ui);
+
+    let mut grid_changed = false;
+    for value in toggle(grid.is_some())
+        .mid_left_of(ids.grid_background)
+        .down(20.0)
+        .w(WIDGET_W)
+        .label("Grid")
+        .set(ids.grid_on, ui)
+    {
+        // A new grid matches the LED panel, a row per strip.
+        let (width, height) = (PIXELS_PER_LED_STRIP as usize, NUM_LED_STRIPS as usize);
+        grid = match value {
+            true => Some(Grid::new(width, height)),
+            false => None,
+        };
+        grid_changed = true;
+    }
+
+    if let Some(ref mut grid) = grid {
+        for value in slider(grid.width as f32, 1.0, 64.0)
+            .down(10.0)
+            .label("Width")
+            .set(ids.grid_width, ui)
+        {
+            grid.width = value as usize;
+            grid_changed = true;
+        }
+
+        for value in slider(grid.height as f32, 1.0, 64.0)
+            .down(10.0)
+            .label("Height")
+            .set(ids.grid_height, ui)
+        {
+            grid.height = value as usize;
+            grid_changed = true;
+        }
+
+        let offset_names: Vec<&str> = grid::ALL.iter().map(|o| o.name()).collect();
+        let offset_idx = grid::ALL.iter().position(|&o| o == grid.offset);
+        for offset_idx in widget::DropDownList::new(&offset_names, offset_idx)
+            .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+            .down(10.0)
+            .max_visible_items(3)
+            .color(WIDGET_COLOUR)
+            .label("Offset Mode")
+            .label_font_size(14)
+            .label_rgb(1.0, 1.0, 1.0)
+            .scrollbar_on_top()
+            .set(ids.grid_offset, ui)
+        {
+            grid.offset = grid::ALL[offset_idx];
+            grid_changed = true;
+        }
+
+        for value in slider(grid.x_velocity, 0.0, 0.2)
+            .down(10.0)
+            .label("X Offset")
+            .set(ids.grid_x_velocity, ui)
+        {
+            grid.x_velocity = value;
+            grid_changed = true;
+        }
+
+        for value in slider(grid.y_velocity, 0.0, 0.2)
+            .down(10.0)
+            .label("Y Offset")
+            .set(ids.grid_y_velocity, ui)
+        {
+            grid.y_velocity = value;
+            grid_changed = true;
+        }
+
+        for value in slider(grid.arms as f32, 1.0, 12.0)
+            .down(10.0)
+            .label("Arms")
+            .set(ids.grid_arms, ui)
+        {
+            grid.arms = value as u32;
+            grid_changed = true;
+        }
+
+        // The pad's y runs upwards while the grid's rows count down from the top.
+        let (cx, cy) = (grid.centre[0], 1.0 - grid.centre[1]);
+        for (x, y) in widget::XYPad::new(cx, 0.0, 1.0, cy, 0.0, 1.0)
+            .w_h(WIDGET_W, 100.0)
+            .down(10.0)
+            .label("Centre")
+            .label_font_size(14)
+            .value_font_size(10)
+            .color(WIDGET_COLOUR)
+            .label_rgb(1.0, 1.0, 1.0)
+            .border(0.0)
+            .set(ids.grid_centre, ui)
+        {
+            grid.centre = [x, 1.0 - y];
+            grid_changed = true;
+        }
+    }
+
+    // The speed and phase spread of the selected layer's oscillators.
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 270.0)
+        .down_from(ids.grid_background, 0.0)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.spread_background, ui);
+
+    text("SPREAD")
+        .mid_top_of(ids.spread_background)
+        .set(ids.spread_title_text, ui);
+
+    let distribution_names: Vec<&str> = spread::ALL.iter().map(|d| d.name()).collect();
+    let distribution_idx = spread::ALL
+        .iter()
+        .position(|d| d.name() == shm.spread.distribution.name());
+    for distribution_idx in widget::DropDownList::new(&distribution_names, distribution_idx)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .mid_left_of(ids.spread_background)
+        .down(20.0)
+        .max_visible_items(5)
+        .color(WIDGET_COLOUR)
+        .label("Distribution")
+        .label_font_size(14)
+        .label_rgb(1.0, 1.0, 1.0)
+        .scrollbar_on_top()
+        .set(ids.spread_distribution, ui)
+    {
+        shm.spread.distribution = spread::ALL[distribution_idx];
+    }
+
+    for value in slider(shm.spread.speed, -1.0, 1.0)
+        .down(10.0)
+        .label("Speed Spread")
+        .set(ids.spread_speed, ui)
+    {
+        shm.spread.speed = value;
+    }
+
+    for value in slider(shm.spread.phase, 0.0, 1.0)
+        .down(10.0)
+        .label("Phase Spread")
+        .set(ids.spread_phase, ui)
+    {
+        shm.spread.phase = value;
+    }
+
+    match &mut shm.spread.distribution {
+        Distribution::Random { seed } => {
+            for value in slider(*seed as f32, 0.0, 1000.0)
+                .down(10.0)
+                .label("Seed")
+                .set(ids.spread_seed, ui)
+            {
+                *seed = value as _;
+            }
+        }
+        Distribution::Curve(EasingType::CubicBezier { x1, y1, x2, y2 }) => {
+            for (x, y) in bezier_pad(*x1, *y1)
+                .down(10.0)
+                .label("P1")
+                .set(ids.spread_p1, ui)
+            {
+                *x1 = x;
+                *y1 = y;
+            }
+
+            for (x, y) in bezier_pad(*x2, *y2)
+                .right(PAD * 0.5)
+                .label("P2")
+                .set(ids.spread_p2, ui)
+            {
+                *x2 = x;
+                *y2 = y;
+            }
+        }
+        _ => (),
+    }
+
+    if resync {
+        layers.resync(&params.clock);
+    }
+
+    // Play, pause, reverse or seek every layer together, just above the tempo clock.
+    let transport = layers.base().transport;
+    widget::Canvas::new()
+        .pad(PAD)
+        .border(0.0)
+        .w_h(COLUMN_W, 170.0)
+        .up_from(ids.tempo_background, 0.0)
+        .rgba(0.2, 0.2, 0.2, 0.5)
+        .set(ids.transport_background, ui);
+
+    text("TRANSPORT")
+        .mid_top_of(ids.transport_background)
+        .set(ids.transport_title_text, ui);
+
+    let play_label = match transport.paused {
+        true => "Play",
+        false => "Pause",
+    };
+    for _click in button(play_label)
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .mid_left_of(ids.transport_background)
+        .down(20.0)
+        .set(ids.play, ui)
+    {
+        for layer in layers.iter_mut() {
+            layer.shm.transport.paused = !transport.paused;
+        }
+    }
+
+    for value in toggle(transport.reverse)
+        .w_h(HALF_WIDGET_W, DEFAULT_WIDGET_H)
+        .right(PAD * 0.5)
+        .label("Reverse")
+        .set(ids.reverse, ui)
+    {
+        for layer in layers.iter_mut() {
+            layer.shm.transport.reverse = value;
+        }
+    }
+
+    // Scrub through a window of time, or type a time in to seek to it exactly. The window only
+    // moves on to the next when the time leaves it, never while it's being dragged.
+    let time = transport.time();
+    let start = params.scrub_start;
+    if time < start || time > start + SCRUB_WINDOW {
+        params.scrub_start = (time / SCRUB_WINDOW).floor() * SCRUB_WINDOW;
+    }
+    let (start, end) = (params.scrub_start, params.scrub_start + SCRUB_WINDOW);
+    let scrub_label = format!("{:.2} s", time);
+    for value in slider(time as f32, start as f32, end as f32)
+        .down_from(ids.play, 10.0)
+        .align_left_of(ids.play)
+        .label(&scrub_label)
+        .set(ids.scrub, ui)
+    {
+        for layer in layers.iter_mut() {
+            layer.shm.transport.seek(value as f64);
+        }
+    }
+
+    for event in widget::TextBox::new(&params.seek)
+        .w_h(WIDGET_W, DEFAULT_WIDGET_H)
+        .down(10.0)
+        .font_size(14)
+        .left_justify()
+        .set(ids.seek, ui)
+    {
+        match event {
+            widget::text_box::Event::Update(text) => params.seek = text,
+            widget::text_box::Event::Enter => match params.seek.trim().parse::<f64>() {
+                Ok(time) => {
+                    for layer in layers.iter_mut() {
+                        layer.shm.transport.seek(time);
+                    }
+                }
+                Err(err) => println!("Failed to seek to {}: {}", params.seek, err),
+            },
+        }
+    }
+
+    // Every layer has the same number of oscillators, laid out in the same grid.
+    if let Some(size) = size {
+        layers.set_size(size);
+    }
+    if grid_changed {
+        layers.set_grid(grid);
+    }
+}
+
+/// Whether one of the text boxes has the keyboard, in which case key presses are being typed.
+pub fn is_typing(ui: &Ui, ids: &Ids) -> bool {
+    let capturing = match ui.global_input().current.widget_capturing_keyboard {
+        Some(id) => id,
+        None => return false,
+    };
+    let graph = ui.widget_graph();
+    [ids.formula, ids.bezier_text, ids.seek]
+        .iter()
+        .any(|&id| id == capturing || graph.does_recursive_depth_edge_exist(id, capturing))
 }
 
 fn slider(val: f32, min: f32, max: f32) -> widget::Slider<'static, f32> {
@@ -205,6 +1748,27 @@ fn toggle(b: bool) -> widget::Toggle<'static> {
         .border(0.0)
 }
 
+// A 2D handle for one of the bezier control points.
+fn bezier_pad(x: f32, y: f32) -> widget::XYPad<'static, f32, f32> {
+    widget::XYPad::new(x, 0.0, 1.0, y, -0.5, 1.5)
+        .w_h(HALF_WIDGET_W, HALF_WIDGET_W)
+        .label_font_size(14)
+        .value_font_size(10)
+        .color(WIDGET_COLOUR)
+        .label_rgb(1.0, 1.0, 1.0)
+        .border(0.0)
+}
+
+// Shorthand for the button style we'll use.
+fn button(label: &str) -> widget::Button<widget::button::Flat> {
+    widget::Button::new()
+        .label(label)
+        .label_font_size(14)
+        .color(WIDGET_COLOUR)
+        .label_rgb(1.0, 1.0, 1.0)
+        .border(0.0)
+}
+
 fn text(s: &str) -> widget::Text {
     widget::Text::new(s).color(color::WHITE)
 }
@@ -0,0 +1,240 @@
+// Layered shm module
+//
+// Several shm engines run at once, each with its own signal, speed, offset and skew. Their
+// phases are combined per oscillator from the bottom layer up, each layer blending onto the
+// layers beneath it by its blend mode and opacity.
+use crate::grid::Grid;
+use crate::shm::Shm;
+use crate::signals::Signal;
+use crate::tempo::{self, Clock};
+use std::time::Duration;
+
+/// Each of the blend modes, in the order they are listed in the GUI.
+pub const ALL: &[Blend] = &[
+    Blend::Add,
+    Blend::Multiply,
+    Blend::Max,
+    Blend::Min,
+    Blend::Difference,
+    Blend::Crossfade,
+];
+
+/// The most layers that can run at once.
+pub const MAX_LAYERS: usize = 4;
+/// The opacity of newly added layers.
+pub const DEFAULT_OPACITY: f32 = 0.5;
+
+/// How a layer combines with the layers beneath it. The modes treat the phases as 0..1 levels,
+/// like the blend modes of an image editor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Blend {
+    Add,
+    Multiply,
+    Max,
+    Min,
+    Difference,
+    // Replace the layers beneath, so that the opacity fades between them
+    Crossfade,
+}
+
+pub struct Layer {
+    pub shm: Shm,
+    pub blend: Blend,
+    // How much of the blended result to mix over the layers beneath, 0..1
+    pub opacity: f32,
+    // Set the speed from the tempo clock
+    pub tempo_sync: bool,
+    // The index within `tempo::DIVISIONS` of the note length a cycle lasts when synced
+    pub division_idx: usize,
+}
+
+pub struct Layers {
+    // The bottom layer first
+    layers: Vec<Layer>,
+    // The layer the GUI is editing
+    selected: usize,
+    // The blended phases of every layer
+    phases: Vec<f32>,
+}
+
+impl Blend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Blend::Add => "Add",
+            Blend::Multiply => "Multiply",
+            Blend::Max => "Max",
+            Blend::Min => "Min",
+            Blend::Difference => "Difference",
+            Blend::Crossfade => "Crossfade",
+        }
+    }
+
+    /// Blend phase `b` onto phase `a`, both -1..1
+    pub fn blend(&self, a: f32, b: f32) -> f32 {
+        let (a, b) = (a * 0.5 + 0.5, b * 0.5 + 0.5);
+        let level = match self {
+            Blend::Add => a + b,
+            Blend::Multiply => a * b,
+            Blend::Max => a.max(b),
+            Blend::Min => a.min(b),
+            Blend::Difference => (a - b).abs(),
+            Blend::Crossfade => b,
+        };
+        level * 2.0 - 1.0
+    }
+}
+
+impl Layer {
+    pub fn new(shm: Shm) -> Self {
+        Layer {
+            shm,
+            blend: Blend::Crossfade,
+            opacity: DEFAULT_OPACITY,
+            tempo_sync: false,
+            division_idx: tempo::DEFAULT_DIVISION,
+        }
+    }
+
+    /// The note length a cycle lasts when synced to the tempo clock
+    pub fn division(&self) -> tempo::Division {
+        tempo::DIVISIONS[self.division_idx]
+    }
+}
+
+impl Layers {
+    /// Start with a single layer running the given shm
+    pub fn new(shm: Shm) -> Self {
+        let phases = vec![0.0; shm.size()];
+        Layers {
+            layers: vec![Layer::new(shm)],
+            selected: 0,
+            phases,
+        }
+    }
+
+    /// The number of layers, which is never less than one as there is always a bottom layer
+    pub fn count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// The index of the layer being edited
+    pub fn selected_idx(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        self.selected = idx.min(self.layers.len() - 1);
+    }
+
+    pub fn selected(&self) -> &Layer {
+        &self.layers[self.selected]
+    }
+
+    pub fn selected_mut(&mut self) -> &mut Layer {
+        &mut self.layers[self.selected]
+    }
+
+    /// The shm of the bottom layer, which the other layers are blended onto
+    pub fn base(&self) -> &Shm {
+        &self.layers[0].shm
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Layer> {
+        self.layers.iter_mut()
+    }
+
+    /// Add a layer above the others, running the given signal with the speed, offset, skew and
+    /// tempo sync of the selected layer, and select it. Does nothing once there are `MAX_LAYERS`.
+    pub fn push(&mut self, signal: Signal) {
+        if self.layers.len() >= MAX_LAYERS {
+            return;
+        }
+        let (tempo_sync, division_idx) = (self.selected().tempo_sync, self.selected().division_idx);
+        let selected = &self.selected().shm;
+        let mut shm = Shm::new(
+            selected.strip_size(),
+            selected.hz,
+            selected.a_velocity,
+            selected.offset_hz,
+        );
+        shm.skew = selected.skew;
+        shm.spread = selected.spread;
+        shm.symmetry = selected.symmetry;
+        shm.transport = selected.transport;
+        shm.set_grid(selected.grid().cloned());
+        shm.set_signal_type(signal);
+        let mut layer = Layer::new(shm);
+        layer.tempo_sync = tempo_sync;
+        layer.division_idx = division_idx;
+        self.layers.push(layer);
+        self.selected = self.layers.len() - 1;
+    }
+
+    /// Remove the selected layer, unless it is the only one.
+    pub fn remove_selected(&mut self) {
+        if self.layers.len() > 1 {
+            self.layers.remove(self.selected);
+            self.selected = self.selected.min(self.layers.len() - 1);
+        }
+    }
+
+    /// Set the speed of every synced layer from the clock, restarting their cycles on the
+    /// downbeat when `downbeat_reset` is set.
+    pub fn follow_clock(&mut self, clock: &Clock, downbeat_reset: bool) {
+        for layer in self.layers.iter_mut().filter(|layer| layer.tempo_sync) {
+            let division = layer.division();
+            layer.shm.hz = clock.hz(division);
+            if downbeat_reset && clock.passed_downbeat(division) {
+                layer.shm.reset_phase();
+            }
+        }
+    }
+
+    /// Line the cycle of every synced layer up with the clock, e.g. after it was nudged.
+    pub fn resync(&mut self, clock: &Clock) {
+        for layer in self.layers.iter_mut().filter(|layer| layer.tempo_sync) {
+            let phase = clock.cycle_phase(layer.division());
+            layer.shm.set_cycle_phase(phase);
+        }
+    }
+
+    /// Lay out the oscillators of every layer as the given grid
+    pub fn set_grid(&mut self, grid: Option<Grid>) {
+        for layer in self.layers.iter_mut() {
+            layer.shm.set_grid(grid.clone());
+        }
+    }
+
+    /// Set the number of oscillators of every layer
+    pub fn set_size(&mut self, size: usize) {
+        for layer in self.layers.iter_mut() {
+            layer.shm.set_size(size);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.base().size()
+    }
+
+    /// The phases of every layer blended together
+    pub fn phases(&self) -> &[f32] {
+        &self.phases
+    }
+
+    /// Advance every layer by the time elapsed since the last update and blend their phases.
+    pub fn update(&mut self, dt: Duration) {
+        for layer in self.layers.iter_mut() {
+            layer.shm.update(dt);
+        }
+
+        let (base, layers) = self.layers.split_first().expect("there is always a layer");
+        self.phases.clear();
+        self.phases.extend_from_slice(base.shm.phases());
+        for layer in layers {
+            let opacity = layer.opacity.clamp(0.0, 1.0);
+            for (a, &b) in self.phases.iter_mut().zip(layer.shm.phases()) {
+                *a += (layer.blend.blend(*a, b) - *a) * opacity;
+            }
+        }
+    }
+}
@@ -1,14 +1,44 @@
+#![recursion_limit = "256"]
+
+mod analysis;
+mod chaos;
+mod envelope;
+mod formula;
+mod grid;
 mod gui;
+mod layer;
+mod names;
+mod preset;
+mod resample;
+mod rhythm;
+mod shaper;
 mod shm;
 mod signals;
-
+mod spread;
+mod spring;
+mod symmetry;
+mod tempo;
+mod transport;
+mod wavetable;
+
+use analysis::Analysis;
+use chaos::Chaos;
+use envelope::Envelope;
+use layer::Layers;
 use nannou::prelude::*;
 use nannou::Ui;
 use nannou_audio::{self as audio, Buffer};
 use nannou_laser as laser;
+use resample::Crossfade;
+use shaper::Chain;
 use shm::Shm;
-use signals::Signal;
+use signals::{BandLimited, Signal};
+use spring::Spring;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
 use std::sync::{mpsc, Arc};
+use tempo::Clock;
+use wavetable::Wavetable;
 
 const PIXELS_PER_LED_STRIP: u16 = 48;
 const DMX_CHANNELS_PER_LED: u16 = 3;
@@ -17,24 +47,108 @@ const STRIPS_PER_UNIVERSE: u16 = 3;
 const LED_ADDRS_PER_UNIVERSE: u16 = ADDRS_PER_STRIP * STRIPS_PER_UNIVERSE;
 const NUM_LED_STRIPS: u16 = 6;
 const TOTAL_LED_PIXELS: u16 = NUM_LED_STRIPS * PIXELS_PER_LED_STRIP;
+// Any UDP datagram sent to this port on localhost fires the envelope, unless `--trigger-addr`
+// gives another address to listen on
+const TRIGGER_PORT: u16 = 9001;
+// The most banks of audio oscillators that fade out at once. Dragging the count replaces the
+// bank every frame, so past this the quietest of them is dropped.
+const MAX_FADING_BANKS: usize = 4;
 
 fn main() {
+    // List the names accepted by `--signal` and exit.
+    if std::env::args().any(|arg| arg == "--list-signals") {
+        let mut signals = signals::ALL.to_vec();
+        signals.extend(signals::combinations());
+        for (category, signals) in names::categorised(&signals) {
+            println!("{}", category);
+            for signal in signals {
+                println!("    {}", signal);
+            }
+        }
+        return;
+    }
+
     nannou::app(model).update(update).run();
 }
 
+// The signal named by the `--signal <name>` command line flag, if any.
+fn signal_arg() -> Option<Signal> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--signal").skip(1);
+    let name = args.next()?;
+    match name.parse() {
+        Ok(signal) => Some(signal),
+        Err(err) => {
+            println!("Ignoring --signal: {}", err);
+            None
+        }
+    }
+}
+
+// The address to listen for triggers on, from `--trigger-addr` or localhost by default.
+fn trigger_addr_arg() -> SocketAddr {
+    let default = SocketAddr::from((Ipv4Addr::LOCALHOST, TRIGGER_PORT));
+    let mut args = std::env::args()
+        .skip_while(|arg| arg != "--trigger-addr")
+        .skip(1);
+    let addr = match args.next() {
+        Some(addr) => addr,
+        None => return default,
+    };
+    match addr.parse() {
+        Ok(addr) => addr,
+        Err(err) => {
+            println!("Ignoring --trigger-addr: {}", err);
+            default
+        }
+    }
+}
+
 pub struct SignalParams {
+    signals: Vec<Signal>,
     signal_names: Vec<String>,
     selected_idx: Option<usize>,
-    pow: f32,
-    min: f32,
-    max: f32,
-    invert: bool, // Invert all of the phases
+    morph_idx: Option<usize>,
+    formula: String,
+    formula_error: Option<String>,
+    // Bezier handles pasted in as `x1, y1, x2, y2`
+    bezier: String,
+    bezier_error: Option<String>,
+    shapers: Chain,
+    // The index of the shaper last chosen to be added to the chain
+    shaper_idx: Option<usize>,
+    // Where the preset is saved to and loaded from
+    preset_path: Option<PathBuf>,
+    normalise: bool, // Fit overshooting signals within -1..1
+    spring_on: bool, // Have springs chase the phases
+    spring: Spring,
+    source: Source, // What drives the phases
+    envelope: Envelope,
+    envelope_shapes: Vec<envelope::Shape>,
+    envelope_names: Vec<String>,
+    envelope_idx: Option<usize>,
+    chaos: Chaos,
+    chaos_idx: Option<usize>,
+    clock: Clock,
+    downbeat_reset: bool, // Restart the synced layers' cycles on the downbeat
+    // The transport time typed in to seek to, in seconds
+    seek: String,
+    // The time at the start of the scrub slider, which shows a fixed window of time from here
+    scrub_start: f64,
+    analysis: Analysis,
     dmx_on: bool,
     laser_on: bool,
     audio_on: bool,
     hue: f32,
 }
 
+/// The source of the phases before they are shaped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Source {
+    Shm,
+    Envelope,
+    Chaos,
+}
+
 struct Model {
     dmx: Dmx,
     audio_host: audio::Host,
@@ -43,11 +157,20 @@ struct Model {
     laser_stream: Option<laser::FrameStream<Laser>>,
     laser_dac_rx: mpsc::Receiver<laser::DetectedDac>,
     detected_laser_dac: Option<laser::DetectedDac>,
-    shm: Shm,
+    trigger_rx: mpsc::Receiver<()>,
+    layers: Layers,
     ui: Ui,
     ids: gui::Ids,
     params: SignalParams,
     phases: Vec<f32>,
+    // Smooths the outputs over changes to the number of phases
+    crossfade: Crossfade,
+    // The signal last sent to the audio thread
+    audio_signal: Option<Signal>,
+    // The number of oscillators last sent to the audio thread
+    audio_count: usize,
+    // The signal that `params.analysis` describes
+    analysed_signal: Signal,
 }
 
 #[derive(Clone)]
@@ -63,6 +186,11 @@ struct Laser {
 
 struct Audio {
     oscillators: Vec<Oscillator>,
+    // The level of the oscillators, rising to 1 as they fade in after the count changes
+    level: f32,
+    // The banks of oscillators from before the count changed, fading out from their levels
+    fading: Vec<(Vec<Oscillator>, f32)>,
+    signal: BandLimited,
 }
 
 struct Dmx {
@@ -75,11 +203,12 @@ fn model(app: &App) -> Model {
         .new_window()
         .with_dimensions(1200, 750)
         .view(view)
+        .key_pressed(key_pressed)
         .build()
         .unwrap();
 
-    let mut shm = Shm::new(TOTAL_LED_PIXELS as usize, 0.1, 0.005, 0.0);
-    shm.set_signal_type(Signal::SINE_IN_OUT);
+    let mut shm = Shm::new(TOTAL_LED_PIXELS as usize, 0.3, 0.005, 0.0);
+    shm.set_signal_type(signal_arg().unwrap_or(Signal::SINE_IN_OUT));
 
     let phases = vec![0.0; shm.size()];
 
@@ -118,6 +247,27 @@ fn model(app: &App) -> Model {
     });
 
     let detected_laser_dac = None;
+
+    // A channel for receiving remote envelope triggers.
+    let (trigger_tx, trigger_rx) = mpsc::channel();
+
+    // Spawn a thread for listening for triggers.
+    let trigger_addr = trigger_addr_arg();
+    std::thread::spawn(move || {
+        let socket = match UdpSocket::bind(trigger_addr) {
+            Ok(socket) => socket,
+            Err(err) => {
+                println!("Failed to listen for triggers on {}: {}", trigger_addr, err);
+                return;
+            }
+        };
+        let mut buf = [0; 64];
+        while socket.recv(&mut buf).is_ok() {
+            if trigger_tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
     let laser_stream = None;
 
     let dmx = Dmx {
@@ -125,13 +275,54 @@ fn model(app: &App) -> Model {
         buffer: vec![],
     };
 
+    // The built-in signals and their combinations, followed by any wavetables found in the
+    // assets directory.
+    let mut signals = signals::ALL.to_vec();
+    signals.extend(signals::combinations());
+    if let Ok(assets) = app.assets_path() {
+        let tables = Wavetable::load_dir(assets.join(wavetable::DIRECTORY));
+        signals.extend(tables.into_iter().map(Signal::Wavetable));
+    }
+    let signal_names = signals.iter().map(|s| s.to_string()).collect();
+    let preset_path = app
+        .assets_path()
+        .ok()
+        .map(|assets| assets.join(preset::DIRECTORY).join(preset::DEFAULT_NAME));
+
+    let envelope_shapes = envelope::shapes();
+    let envelope_names = envelope_shapes.iter().map(|s| s.name()).collect();
+
+    let analysed_signal = shm.signal_type().clone();
+    let layers = Layers::new(shm);
+    let analysis = Analysis::new(&analysed_signal, analysis::DEFAULT_RESOLUTION);
+
     let params = SignalParams {
-        signal_names: signals::Signal::all_names(),
+        signals,
+        signal_names,
         selected_idx: None,
-        pow: 1.0,
-        min: -1.0,
-        max: 1.0,
-        invert: false,
+        morph_idx: None,
+        formula: "sin(p*2) * 0.5 + tri(p + 0.25) * 0.5".to_string(),
+        formula_error: None,
+        bezier: "0.25, 0.1, 0.25, 1".to_string(),
+        bezier_error: None,
+        shapers: Chain::default(),
+        shaper_idx: None,
+        preset_path,
+        normalise: false,
+        analysis,
+        spring_on: false,
+        spring: Spring::new(),
+        source: Source::Shm,
+        envelope: Envelope::new(envelope::Shape::AHD),
+        envelope_shapes,
+        envelope_names,
+        envelope_idx: None,
+        chaos: Chaos::new(chaos::ALL[0]),
+        chaos_idx: None,
+        clock: Clock::new(tempo::DEFAULT_BPM),
+        downbeat_reset: false,
+        seek: "0.0".to_string(),
+        scrub_start: 0.0,
         dmx_on: false,
         laser_on: true,
         audio_on: false,
@@ -146,18 +337,36 @@ fn model(app: &App) -> Model {
         laser_stream,
         laser_dac_rx,
         detected_laser_dac,
-        shm,
+        trigger_rx,
+        layers,
         ui,
         ids,
         params,
         phases,
+        crossfade: Crossfade::new(),
+        audio_signal: None,
+        audio_count: 0,
+        analysed_signal,
     }
 }
 
-fn update(_app: &App, m: &mut Model, _update: Update) {
+fn update(_app: &App, m: &mut Model, update: Update) {
     // Apply the GUI update.
     let ui = m.ui.set_widgets();
-    gui::update(ui, &mut m.ids, &mut m.params, &mut m.shm);
+    gui::update(ui, &mut m.ids, &mut m.params, &mut m.layers);
+
+    // Re-analyse the signal of the bottom layer whenever it changes. Like the audio, the
+    // analysis and normalising follow the bottom layer rather than the blend of every layer.
+    let signal = m.layers.base().signal_type();
+    if m.analysed_signal != *signal {
+        m.analysed_signal = signal.clone();
+        m.params.analysis = Analysis::new(&m.analysed_signal, analysis::DEFAULT_RESOLUTION);
+    }
+
+    // Fire the envelope for any remote triggers.
+    for () in m.trigger_rx.try_iter() {
+        m.params.envelope.trigger();
+    }
 
     // First, check for new laser DACs.
     for dac in m.laser_dac_rx.try_recv() {
@@ -192,13 +401,15 @@ fn update(_app: &App, m: &mut Model, _update: Update) {
 
     // Create or destroy the audio stream if necessary.
     if m.audio_stream.is_none() && m.params.audio_on {
-        let oscillators = (0..m.phases.len())
-            .map(|_| Oscillator {
-                phase: 0.0,
-                hz: 100.0,
-            })
-            .collect();
-        let audio_model = Audio { oscillators };
+        let signal = m.layers.base().signal_type().clone();
+        m.audio_signal = Some(signal.clone());
+        m.audio_count = m.phases.len();
+        let audio_model = Audio {
+            oscillators: new_bank(m.audio_count),
+            level: 1.0,
+            fading: Vec::with_capacity(MAX_FADING_BANKS),
+            signal: BandLimited::new(signal),
+        };
         let stream = m
             .audio_host
             .new_output_stream(audio_model)
@@ -220,39 +431,72 @@ fn update(_app: &App, m: &mut Model, _update: Update) {
         m.dmx.source.take();
     }
 
-    // Update the simple harmonic motion.
-    m.shm.update();
-    if m.params.selected_idx.is_some() {
-        m.shm
-            .set_signal_type(signals::ALL[m.params.selected_idx.unwrap()]);
+    // Follow the tempo clock with every synced layer, restarting their cycles on the downbeat
+    // if enabled.
+    m.params.clock.update(update.since_last);
+    m.layers
+        .follow_clock(&m.params.clock, m.params.downbeat_reset);
+
+    // Update the simple harmonic motion of every layer and blend them together.
+    m.layers.update(update.since_last);
+
+    // Run the envelope along the strip and the chaotic systems alongside the SHM.
+    let size = m.layers.size();
+    let a_velocity = m.layers.base().a_velocity;
+    let dt = update.since_last;
+    m.params.envelope.update(size, a_velocity, dt);
+    if m.params.source == Source::Chaos {
+        m.params.chaos.update(size, dt);
     }
+    let phases = match m.params.source {
+        Source::Shm => m.layers.phases(),
+        Source::Envelope => m.params.envelope.levels(),
+        Source::Chaos => m.params.chaos.levels(),
+    };
 
-    // Apply the invert and pow GUI controls to the SHM phases to get our actual phases.
-    m.phases = m
-        .shm
-        .phases()
-        .iter()
-        .map(|p| {
-            let mut phase = map_range(p.clone(), -1.0, 1.0, 0.0, 1.0);
-            phase = phase.powf(m.params.pow);
-            match m.params.invert {
-                true => map_range(phase, 0.0, 1.0, m.params.max, m.params.min),
-                false => map_range(phase, 0.0, 1.0, m.params.min, m.params.max),
-            }
-        })
-        .collect();
+    // Let the springs chase the phases when enabled.
+    let phases = match m.params.spring_on {
+        true => {
+            m.params.spring.update(phases, update.since_last);
+            m.params.spring.positions()
+        }
+        false => phases,
+    };
+
+    // Normalise the phases and run them through the shaper chain to get our actual phases.
+    let (normalise, analysis) = (m.params.normalise, &m.params.analysis);
+    m.phases.clear();
+    m.phases.extend(phases.iter().map(|p| match normalise {
+        true => analysis.normalise(*p),
+        false => *p,
+    }));
+    m.params.shapers.process(&mut m.phases, update.since_last);
+    m.crossfade.process(&mut m.phases, update.since_last);
 
     // If we have a DMX source, send data over it!
     if let (Some(dmx_source), true) = (&m.dmx.source, m.params.dmx_on) {
         m.dmx.buffer.clear();
 
-        // Use the pixel index to determine which phase to select for our brightness values.
+        // Use the pixel index to determine which phase to select for our brightness values. With a
+        // grid, each strip is a row of the panel and the phase is read by its column and row.
+        let grid = m.layers.base().grid();
         for i in 0..TOTAL_LED_PIXELS {
-            let phase_ix = ((i as f64 / TOTAL_LED_PIXELS as f64) * m.phases.len() as f64) as usize;
-            let phase = m.phases[phase_ix] * 0.5 + 0.5;
+            let phase = match grid {
+                Some(grid) => {
+                    let x = (i % PIXELS_PER_LED_STRIP) as f32 / PIXELS_PER_LED_STRIP as f32;
+                    let y = (i / PIXELS_PER_LED_STRIP) as f32 / NUM_LED_STRIPS as f32;
+                    grid.sample(&m.phases, x, y)
+                }
+                None => {
+                    let phase_ix =
+                        ((i as f64 / TOTAL_LED_PIXELS as f64) * m.phases.len() as f64) as usize;
+                    m.phases[phase_ix]
+                }
+            };
+            let phase = phase * 0.5 + 0.5;
             let c: Rgb = hsl(m.params.hue, 1.0, phase).into();
             let lc: LinSrgb = c.into_linear();
-            let float_to_byte = |p: f32| -> u8 { (p * std::u8::MAX as f32) as u8 };
+            let float_to_byte = |p: f32| -> u8 { (p * u8::MAX as f32) as u8 };
             let rgb = [
                 float_to_byte(lc.red),
                 float_to_byte(lc.green),
@@ -282,15 +526,32 @@ fn update(_app: &App, m: &mut Model, _update: Update) {
     // Send our phase data over to the audio thead
     if let Some(ref audio_stream) = m.audio_stream {
         let phases = m.phases.clone();
+
+        // Only send the signal when it changes, as band-limiting it measures the signal.
+        let base_signal = m.layers.base().signal_type();
+        let signal = if m.audio_signal.as_ref() != Some(base_signal) {
+            m.audio_signal = Some(base_signal.clone());
+            Some(BandLimited::new(base_signal.clone()))
+        } else {
+            None
+        };
+
+        // Build a new bank here when the count changes, so the audio thread needn't allocate.
+        let bank = if m.audio_count != phases.len() {
+            m.audio_count = phases.len();
+            Some(new_bank(m.audio_count))
+        } else {
+            None
+        };
+
         audio_stream
             .send(move |audio| {
-                audio.oscillators.resize(
-                    phases.len(),
-                    Oscillator {
-                        phase: 0.0,
-                        hz: 0.0,
-                    },
-                );
+                if let Some(signal) = signal {
+                    audio.signal = signal;
+                }
+                if let Some(bank) = bank {
+                    swap_bank(audio, bank);
+                }
 
                 for (osc, phase) in audio.oscillators.iter_mut().zip(phases) {
                     osc.hz = map_range(phase as f64, -1.0, 1.0, 100.0, 1600.0);
@@ -313,28 +574,54 @@ fn update(_app: &App, m: &mut Model, _update: Update) {
     }
 }
 
+fn key_pressed(_app: &App, m: &mut Model, key: Key) {
+    // Fire the envelope with the space bar, unless it's being typed into a text box.
+    if key == Key::Space && !gui::is_typing(&m.ui, &m.ids) {
+        m.params.envelope.trigger();
+    }
+}
+
 fn view(app: &App, m: &Model, frame: &Frame) {
     let draw = app.draw();
     draw.background().rgb(0.1, 0.1, 0.1);
 
     let win = app.window_rect();
 
-    let radius = win.w() / m.shm.size() as f32;
-    let height = win.h() / 2.0 - 20.0;
-
-    m.phases.iter().enumerate().for_each(|(i, &phase)| {
-        let x = map_range(i, 0, m.phases.len(), win.left(), win.right());
-
-        draw.line()
-            .hsv(m.params.hue, 1.0, 1.0)
-            .start(Point2::new(x, 0.0))
-            .end(Point2::new(x, phase * height));
-
-        draw.ellipse()
-            .hsv(m.params.hue, 1.0, 1.0)
-            .x_y(x, phase * height)
-            .w_h(radius, radius);
-    });
+    match m.layers.base().grid() {
+        // Draw a grid as a cell per oscillator, brighter for higher phases.
+        Some(grid) => {
+            let cell_w = win.w() / grid.width as f32;
+            let cell_h = win.h() / grid.height as f32;
+            for (i, &phase) in m.phases.iter().enumerate() {
+                let (x, y) = grid.cell(i);
+                draw.rect()
+                    .hsv(m.params.hue, 1.0, phase * 0.5 + 0.5)
+                    .x_y(
+                        win.left() + cell_w * (x as f32 + 0.5),
+                        win.top() - cell_h * (y as f32 + 0.5),
+                    )
+                    .w_h(cell_w - 1.0, cell_h - 1.0);
+            }
+        }
+        None => {
+            let radius = win.w() / m.layers.size() as f32;
+            let height = win.h() / 2.0 - 20.0;
+
+            m.phases.iter().enumerate().for_each(|(i, &phase)| {
+                let x = map_range(i, 0, m.phases.len(), win.left(), win.right());
+
+                draw.line()
+                    .hsv(m.params.hue, 1.0, 1.0)
+                    .start(Point2::new(x, 0.0))
+                    .end(Point2::new(x, phase * height));
+
+                draw.ellipse()
+                    .hsv(m.params.hue, 1.0, 1.0)
+                    .x_y(x, phase * height)
+                    .w_h(radius, radius);
+            });
+        }
+    }
 
     draw.to_frame(app, &frame).unwrap();
 
@@ -342,25 +629,85 @@ fn view(app: &App, m: &Model, frame: &Frame) {
     m.ui.draw_to_frame(app, &frame).unwrap();
 }
 
+// A bank of `size` oscillators, spread around the cycle so that they don't start in phase.
+fn new_bank(size: usize) -> Vec<Oscillator> {
+    (0..size)
+        .map(|i| Oscillator {
+            phase: i as f64 / size as f64,
+            hz: 100.0,
+        })
+        .collect()
+}
+
+// Crossfade to the given bank, fading the current one out as the new one fades in. Each new
+// oscillator carries on from the nearest one along the strip, except that only the first to
+// share it takes its phase, so that the copies don't sum in phase. Those that share one are
+// neighbours, as the nearest only moves along the strip.
+fn swap_bank(audio: &mut Audio, mut bank: Vec<Oscillator>) {
+    let (size, len) = (bank.len(), audio.oscillators.len());
+    if len > 0 {
+        let mut last = None;
+        for (i, osc) in bank.iter_mut().enumerate() {
+            let j = resample::nearest(i, size, len);
+            if last != Some(j) {
+                osc.phase = audio.oscillators[j].phase;
+            }
+            last = Some(j);
+        }
+    }
+
+    let old = std::mem::replace(&mut audio.oscillators, bank);
+    if audio.fading.len() == MAX_FADING_BANKS {
+        let mut quietest = 0;
+        for (i, &(_, level)) in audio.fading.iter().enumerate() {
+            if level < audio.fading[quietest].1 {
+                quietest = i;
+            }
+        }
+        audio.fading.swap_remove(quietest);
+    }
+    audio.fading.push((old, audio.level));
+    audio.level = 0.0;
+}
+
 // A function that renders the given `Audio` to the given `Buffer`, returning the result of both.
 fn audio(audio: &mut Audio, buffer: &mut Buffer) {
     let sample_rate = buffer.sample_rate() as f64;
     let volume = 0.5;
+    let step = 1.0 / (resample::CROSSFADE_TIME * sample_rate as f32);
     for frame in buffer.frames_mut() {
-        let mut output = 0.0;
-        for osc in audio.oscillators.iter_mut() {
-            let sine_amp = (2.0 * PI * osc.phase as f32).sin() as f32;
-            osc.phase += osc.hz / sample_rate;
-            osc.phase %= sample_rate;
-            output += sine_amp;
+        let signal = &audio.signal;
+        let mut output = render_bank(&mut audio.oscillators, signal, sample_rate) * audio.level;
+        for (oscillators, level) in audio.fading.iter_mut() {
+            output += render_bank(oscillators, signal, sample_rate) * *level;
+            *level -= step;
         }
-        output = (output / audio.oscillators.len() as f32) * volume;
+        audio.fading.retain(|&(_, level)| level > 0.0);
+        audio.level = (audio.level + step).min(1.0);
+        output *= volume;
         for channel in frame {
             *channel = output;
         }
     }
 }
 
+// Advance each oscillator of the bank by one sample, returning their average.
+fn render_bank(oscillators: &mut [Oscillator], signal: &BandLimited, sample_rate: f64) -> f32 {
+    if oscillators.is_empty() {
+        return 0.0;
+    }
+    let mut output = 0.0;
+    for osc in oscillators.iter_mut() {
+        // Render the signal band-limited to the oscillator's frequency.
+        let dt = osc.hz / sample_rate;
+        let amp = signal.amp(osc.phase as f32, dt as f32);
+        osc.phase += dt;
+        osc.phase %= 1.0;
+        output += amp;
+    }
+    output / oscillators.len() as f32
+}
+
 fn laser(laser: &mut Laser, frame: &mut laser::Frame) {
     let points = laser.positions.iter().enumerate().map(|(i, y)| {
         let x = map_range(i, 0, laser.positions.len(), 1.0, -1.0);
@@ -0,0 +1,793 @@
+// Stable names for signals
+//
+// Every signal has a name that can be written with `Display` and read back with `FromStr`, for
+// use by presets, remote control and command line flags. Reading back the name of a signal
+// gives the same signal. The built-in signals are named by category and snake case variant:
+//
+// - `lfo.sine`, `lfo.triangle`, `lfo.sawtooth`, `lfo.square`
+// - `noise.value`, `noise.perlin`, `noise.sample_and_hold`, `noise.random_walk`
+// - `ease.back_in` ... `ease.sine_out`, `ease.power_in_out` etc, one for each `EasingType`
+// - `curve`, the default breakpoint curve
+// - `rhythm.euclidean` and `rhythm.sequencer`
+//
+// The name alone gives the signal with its default parameters. Any other parameters are
+// written after the name in parentheses:
+//
+// - `lfo.square(pulse_width)`
+// - `noise.value(seed, steps)`, and the same for the other noise signals
+// - `ease.back_in(overshoot)`, `ease.elastic_in(amplitude, period)`, `ease.power_in(exponent)`
+// - `ease.cubic_bezier(x1, y1, x2, y2)`, which also accepts the CSS `cubic-bezier(..)` form
+// - `curve(phase value interpolation, ..)` with a breakpoint per argument, where the
+//   interpolation is `step`, `linear` or the name of an easing
+// - `rhythm.euclidean(pulses, steps, rotation, gate)`, where the gate may be left off
+// - `rhythm.sequencer(level, level glide, ..)` with a step per argument
+//
+// The remaining signals carry their definition in their name:
+//
+// - `wavetable.<name>`, a WAV file in `assets/wavetables`, followed by `(position,
+//   interpolation)` unless they are `0` and `linear`. The samples are read from the file, so a
+//   wavetable only reads back the same if it was loaded from there.
+// - `formula:<formula>`, e.g. `formula:sin(p * 2) * 0.5`
+// - `sum(a, b, ..)`, `product(..)`, `min(..)`, `max(..)`, `scale(signal, gain, bias)`,
+//   `crossfade(a, b, mix)` and `phase_mod(carrier, modulator, depth)`
+//
+// Names are matched ignoring case, and the category may be left off a built-in name when it
+// is unambiguous, e.g. `sine_in_out`. See `ALIASES` for the short names.
+use crate::formula::{self, Formula};
+use crate::rhythm::{self, Rhythm, Sequencer, Step};
+use crate::signals::{
+    self, Breakpoint, Combine, Curve, EasingType, Interpolation, LfoType, Signal,
+};
+use crate::wavetable::{self, Wavetable};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// The categories of signal in the order they are listed.
+pub const CATEGORIES: &[&str] = &[
+    "lfo",
+    "noise",
+    "ease",
+    "curve",
+    "rhythm",
+    "wavetable",
+    "combine",
+    "formula",
+];
+
+/// Short names for some of the built-in signals.
+pub const ALIASES: &[(&str, &str)] = &[
+    ("sin", "lfo.sine"),
+    ("tri", "lfo.triangle"),
+    ("saw", "lfo.sawtooth"),
+    ("sqr", "lfo.square"),
+    ("pulse", "lfo.square"),
+    ("noise", "noise.value"),
+    ("perlin", "noise.perlin"),
+    ("s&h", "noise.sample_and_hold"),
+    ("walk", "noise.random_walk"),
+];
+
+#[derive(Debug)]
+pub enum ParseSignalError {
+    // No signal has the given name
+    Unknown(String),
+    // A combination was given the wrong number or kind of arguments
+    InvalidArguments(String),
+    Formula(formula::ParseError),
+    Wavetable(wavetable::LoadError),
+}
+
+impl Signal {
+    /// The category of the signal, the first part of its name.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Signal::Lfo(lfo_type) => match lfo_type {
+                LfoType::Sine | LfoType::Triangle | LfoType::Sawtooth | LfoType::Square { .. } => {
+                    "lfo"
+                }
+                _ => "noise",
+            },
+            Signal::Ease(_) => "ease",
+            Signal::Curve(_) => "curve",
+            Signal::Rhythm(_) => "rhythm",
+            Signal::Wavetable(_) => "wavetable",
+            Signal::Combine(_) => "combine",
+            Signal::Formula(_) => "formula",
+        }
+    }
+}
+
+/// Group the given signals by category, in the order of `CATEGORIES`.
+pub fn categorised(signals: &[Signal]) -> Vec<(&'static str, Vec<&Signal>)> {
+    CATEGORIES
+        .iter()
+        .map(|&category| {
+            let signals = signals.iter().filter(|s| s.category() == category);
+            (category, signals.collect::<Vec<_>>())
+        })
+        .filter(|(_, signals)| !signals.is_empty())
+        .collect()
+}
+
+impl LfoType {
+    /// The stable name of the lfo, e.g. `lfo.triangle`
+    pub fn id(&self) -> &'static str {
+        match self {
+            LfoType::Sine => "lfo.sine",
+            LfoType::Triangle => "lfo.triangle",
+            LfoType::Sawtooth => "lfo.sawtooth",
+            LfoType::Square { .. } => "lfo.square",
+            LfoType::ValueNoise { .. } => "noise.value",
+            LfoType::PerlinNoise { .. } => "noise.perlin",
+            LfoType::SampleAndHold { .. } => "noise.sample_and_hold",
+            LfoType::RandomWalk { .. } => "noise.random_walk",
+        }
+    }
+}
+
+impl EasingType {
+    /// The stable name of the easing, e.g. `ease.sine_in_out`
+    pub fn id(&self) -> String {
+        format!("ease.{}", snake_case(self.name()))
+    }
+}
+
+/// Convert a variant name such as `SineInOut` to `sine_in_out`.
+pub fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+impl Signal {
+    /// The name of the signal without its parameters, e.g. `lfo.square` or `sum`
+    pub fn id(&self) -> String {
+        match self {
+            Signal::Lfo(lfo_type) => lfo_type.id().to_string(),
+            Signal::Ease(ease_type) => ease_type.id(),
+            Signal::Curve(_) => "curve".to_string(),
+            Signal::Rhythm(rhythm) => rhythm.id().to_string(),
+            Signal::Wavetable(table) => format!("wavetable.{}", table.name()),
+            Signal::Combine(combine) => combine.id().to_string(),
+            Signal::Formula(_) => "formula".to_string(),
+        }
+    }
+
+    // The parameters of a built-in signal in the order they are written after its name.
+    fn args(&self) -> Vec<String> {
+        let list = |args: &[&dyn fmt::Display]| args.iter().map(|a| a.to_string()).collect();
+        match self {
+            Signal::Lfo(LfoType::Square { pulse_width }) => list(&[pulse_width]),
+            Signal::Lfo(LfoType::ValueNoise { seed, steps })
+            | Signal::Lfo(LfoType::PerlinNoise { seed, steps })
+            | Signal::Lfo(LfoType::SampleAndHold { seed, steps })
+            | Signal::Lfo(LfoType::RandomWalk { seed, steps }) => list(&[seed, steps]),
+            Signal::Ease(EasingType::BackIn { overshoot })
+            | Signal::Ease(EasingType::BackInOut { overshoot })
+            | Signal::Ease(EasingType::BackInOutS { overshoot })
+            | Signal::Ease(EasingType::BackInS { overshoot })
+            | Signal::Ease(EasingType::BackOut { overshoot })
+            | Signal::Ease(EasingType::BackOutS { overshoot }) => list(&[overshoot]),
+            Signal::Ease(EasingType::ElasticIn { amplitude, period })
+            | Signal::Ease(EasingType::ElasticInOut { amplitude, period })
+            | Signal::Ease(EasingType::ElasticOut { amplitude, period }) => {
+                list(&[amplitude, period])
+            }
+            Signal::Ease(EasingType::PowerIn { exponent })
+            | Signal::Ease(EasingType::PowerInOut { exponent })
+            | Signal::Ease(EasingType::PowerOut { exponent }) => list(&[exponent]),
+            Signal::Ease(EasingType::CubicBezier { x1, y1, x2, y2 }) => list(&[x1, y1, x2, y2]),
+            Signal::Curve(curve) => curve
+                .points()
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{} {} {}",
+                        p.phase,
+                        p.value,
+                        interpolation_name(&p.interpolation)
+                    )
+                })
+                .collect(),
+            Signal::Rhythm(Rhythm::Euclidean {
+                pulses,
+                steps,
+                rotation,
+                gate,
+            }) => list(&[pulses, steps, rotation, gate]),
+            Signal::Rhythm(Rhythm::Sequencer(sequencer)) => sequencer
+                .steps
+                .iter()
+                .map(|step| match step.glide {
+                    true => format!("{} glide", step.level),
+                    false => step.level.to_string(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+// The name of the interpolation to the next breakpoint of a curve.
+fn interpolation_name(interpolation: &Interpolation) -> String {
+    match interpolation {
+        Interpolation::Step => "step".to_string(),
+        Interpolation::Linear => "linear".to_string(),
+        Interpolation::Ease(ease_type) => Signal::Ease(*ease_type).to_string(),
+    }
+}
+
+// The name of the interpolation between the samples of a wavetable.
+fn table_interpolation_name(interpolation: wavetable::Interpolation) -> &'static str {
+    match interpolation {
+        wavetable::Interpolation::Linear => "linear",
+        wavetable::Interpolation::Cubic => "cubic",
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Signal::Wavetable(table) => {
+                write!(f, "{}", self.id())?;
+                if table.position != 0.0 || table.interpolation != wavetable::Interpolation::Linear
+                {
+                    let interpolation = table_interpolation_name(table.interpolation);
+                    write!(f, "({}, {})", table.position, interpolation)?;
+                }
+                Ok(())
+            }
+            Signal::Combine(combine) => write!(f, "{}", combine),
+            Signal::Formula(formula) => write!(f, "formula:{}", formula.source()),
+            // The built-in signals are written with their parameters unless they're the defaults.
+            _ => match signals::ALL.contains(self) {
+                true => write!(f, "{}", self.id()),
+                false => write!(f, "{}({})", self.id(), self.args().join(", ")),
+            },
+        }
+    }
+}
+
+impl Combine {
+    /// The name of the combination without its children, e.g. `sum`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Combine::Sum(_) => "sum",
+            Combine::Product(_) => "product",
+            Combine::Min(_) => "min",
+            Combine::Max(_) => "max",
+            Combine::Scale { .. } => "scale",
+            Combine::Crossfade { .. } => "crossfade",
+            Combine::PhaseMod { .. } => "phase_mod",
+        }
+    }
+}
+
+impl fmt::Display for Combine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |signals: &[Signal]| {
+            let names: Vec<String> = signals.iter().map(|s| s.to_string()).collect();
+            names.join(", ")
+        };
+        match self {
+            Combine::Sum(signals) => write!(f, "sum({})", list(signals)),
+            Combine::Product(signals) => write!(f, "product({})", list(signals)),
+            Combine::Min(signals) => write!(f, "min({})", list(signals)),
+            Combine::Max(signals) => write!(f, "max({})", list(signals)),
+            Combine::Scale { signal, gain, bias } => {
+                write!(f, "scale({}, {}, {})", signal, gain, bias)
+            }
+            Combine::Crossfade { a, b, mix } => write!(f, "crossfade({}, {}, {})", a, b, mix),
+            Combine::PhaseMod {
+                carrier,
+                modulator,
+                depth,
+            } => write!(f, "phase_mod({}, {}, {})", carrier, modulator, depth),
+        }
+    }
+}
+
+impl FromStr for Signal {
+    type Err = ParseSignalError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        // Signals that carry their definition in their name.
+        if let Some(source) = strip_prefix(s, "formula:") {
+            return Ok(Signal::Formula(Formula::parse(source)?));
+        }
+        if let Some(name) = strip_prefix(s, "wavetable.") {
+            return parse_wavetable(name);
+        }
+
+        // The built-in signals followed by their parameters, or combinations.
+        if let Some((name, args)) = split_call(s) {
+            let name = name.to_lowercase();
+            return match builtin(&name) {
+                Some(signal) => with_args(signal, args),
+                None => parse_combine(&name, args),
+            };
+        }
+
+        builtin(&s.to_lowercase()).ok_or_else(|| ParseSignalError::Unknown(s.to_string()))
+    }
+}
+
+// The built-in signal with the given lowercase name, alias or name without category.
+fn builtin(name: &str) -> Option<Signal> {
+    let name = ALIASES
+        .iter()
+        .find(|&&(alias, _)| alias == name)
+        .map(|&(_, id)| id)
+        .unwrap_or(name);
+    signals::ALL
+        .iter()
+        .find(|signal| {
+            let id = signal.id();
+            id == name || id.split_once('.').map(|(_, short)| short) == Some(name)
+        })
+        .cloned()
+}
+
+// Strip the given lower case prefix from the text, ignoring the case of the text.
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+// Parse a number, rejecting `NaN` and the infinities just as `Curve::try_from` does.
+fn parse_number(s: &str) -> Option<f32> {
+    s.parse().ok().filter(|n: &f32| n.is_finite())
+}
+
+// Split `name(a, b)` into its name and the text between the outer parentheses.
+fn split_call(s: &str) -> Option<(&str, &str)> {
+    let open = s.find('(')?;
+    if !s.ends_with(')') {
+        return None;
+    }
+    Some((s[..open].trim(), &s[open + 1..s.len() - 1]))
+}
+
+// Split the arguments of a call at the commas that aren't nested within parentheses.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if !args[start..].trim().is_empty() {
+        parts.push(args[start..].trim());
+    }
+    parts
+}
+
+/// Parse bezier handles as they're written in other tools, either as CSS `cubic-bezier(x1, y1,
+/// x2, y2)` or as the four numbers alone separated by commas or spaces.
+pub fn parse_bezier(s: &str) -> Option<EasingType> {
+    let s = s.trim();
+    let args = split_call(s).map(|(_, args)| args).unwrap_or(s);
+    let numbers = args
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(parse_number)
+        .collect::<Option<Vec<_>>>()?;
+    match numbers[..] {
+        [x1, y1, x2, y2] => Some(EasingType::CubicBezier { x1, y1, x2, y2 }),
+        _ => None,
+    }
+}
+
+// Apply the parameters written after the name of a built-in signal.
+fn with_args(signal: Signal, args: &str) -> Result<Signal, ParseSignalError> {
+    let name = signal.id();
+    let invalid = || ParseSignalError::InvalidArguments(name.clone());
+    if let Signal::Ease(EasingType::CubicBezier { .. }) = signal {
+        return parse_bezier(args).map(Signal::Ease).ok_or_else(invalid);
+    }
+
+    let args = split_args(args);
+    let number = |i: usize| -> Result<f32, ParseSignalError> {
+        let arg = args.get(i).ok_or_else(invalid)?;
+        parse_number(arg).ok_or_else(invalid)
+    };
+    let count = |i: usize| -> Result<u32, ParseSignalError> {
+        let arg = args.get(i).ok_or_else(invalid)?;
+        arg.parse().map_err(|_| invalid())
+    };
+    let expect_args = |n: usize| match args.len() == n {
+        true => Ok(()),
+        false => Err(invalid()),
+    };
+
+    let signal = match signal {
+        Signal::Lfo(lfo_type) => {
+            let noise = || -> Result<(u32, u32), ParseSignalError> {
+                expect_args(2)?;
+                Ok((count(0)?, count(1)?))
+            };
+            Signal::Lfo(match lfo_type {
+                LfoType::Square { .. } => {
+                    expect_args(1)?;
+                    LfoType::Square {
+                        pulse_width: number(0)?,
+                    }
+                }
+                LfoType::ValueNoise { .. } => {
+                    let (seed, steps) = noise()?;
+                    LfoType::ValueNoise { seed, steps }
+                }
+                LfoType::PerlinNoise { .. } => {
+                    let (seed, steps) = noise()?;
+                    LfoType::PerlinNoise { seed, steps }
+                }
+                LfoType::SampleAndHold { .. } => {
+                    let (seed, steps) = noise()?;
+                    LfoType::SampleAndHold { seed, steps }
+                }
+                LfoType::RandomWalk { .. } => {
+                    let (seed, steps) = noise()?;
+                    LfoType::RandomWalk { seed, steps }
+                }
+                _ => return Err(invalid()),
+            })
+        }
+        Signal::Ease(ease_type) => {
+            let one = || -> Result<f32, ParseSignalError> {
+                expect_args(1)?;
+                number(0)
+            };
+            let two = || -> Result<(f32, f32), ParseSignalError> {
+                expect_args(2)?;
+                Ok((number(0)?, number(1)?))
+            };
+            Signal::Ease(match ease_type {
+                EasingType::BackIn { .. } => EasingType::BackIn { overshoot: one()? },
+                EasingType::BackInOut { .. } => EasingType::BackInOut { overshoot: one()? },
+                EasingType::BackInOutS { .. } => EasingType::BackInOutS { overshoot: one()? },
+                EasingType::BackInS { .. } => EasingType::BackInS { overshoot: one()? },
+                EasingType::BackOut { .. } => EasingType::BackOut { overshoot: one()? },
+                EasingType::BackOutS { .. } => EasingType::BackOutS { overshoot: one()? },
+                EasingType::ElasticIn { .. } => {
+                    let (amplitude, period) = two()?;
+                    EasingType::ElasticIn { amplitude, period }
+                }
+                EasingType::ElasticInOut { .. } => {
+                    let (amplitude, period) = two()?;
+                    EasingType::ElasticInOut { amplitude, period }
+                }
+                EasingType::ElasticOut { .. } => {
+                    let (amplitude, period) = two()?;
+                    EasingType::ElasticOut { amplitude, period }
+                }
+                EasingType::PowerIn { .. } => EasingType::PowerIn { exponent: one()? },
+                EasingType::PowerInOut { .. } => EasingType::PowerInOut { exponent: one()? },
+                EasingType::PowerOut { .. } => EasingType::PowerOut { exponent: one()? },
+                _ => return Err(invalid()),
+            })
+        }
+        Signal::Curve(_) => {
+            let points = args
+                .iter()
+                .map(|arg| parse_breakpoint(arg).ok_or_else(invalid));
+            let points = points.collect::<Result<Vec<_>, _>>()?;
+            Signal::Curve(Curve::try_from(points).map_err(|_| invalid())?)
+        }
+        Signal::Rhythm(Rhythm::Euclidean { .. }) => {
+            if args.len() != 3 && args.len() != 4 {
+                return Err(invalid());
+            }
+            let gate = match args.len() {
+                4 => number(3)?,
+                _ => rhythm::DEFAULT_GATE,
+            };
+            Signal::Rhythm(Rhythm::Euclidean {
+                pulses: count(0)?,
+                steps: count(1)?,
+                rotation: count(2)?,
+                gate,
+            })
+        }
+        Signal::Rhythm(Rhythm::Sequencer(_)) => {
+            let steps = args.iter().map(|arg| parse_step(arg).ok_or_else(invalid));
+            Signal::Rhythm(Rhythm::Sequencer(Sequencer {
+                steps: steps.collect::<Result<Vec<_>, _>>()?.into(),
+            }))
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(signal)
+}
+
+// Split the first word from the rest of the text.
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    match s.find(char::is_whitespace) {
+        Some(end) => (&s[..end], s[end..].trim()),
+        None => (s, ""),
+    }
+}
+
+// Parse a breakpoint of a curve written as `phase value interpolation`.
+fn parse_breakpoint(s: &str) -> Option<Breakpoint> {
+    let (phase, rest) = split_word(s);
+    let (value, interpolation) = split_word(rest);
+    let interpolation = match &interpolation.to_lowercase()[..] {
+        "step" => Interpolation::Step,
+        "linear" => Interpolation::Linear,
+        name => match name.parse() {
+            Ok(Signal::Ease(ease_type)) => Interpolation::Ease(ease_type),
+            _ => return None,
+        },
+    };
+    Some(Breakpoint {
+        phase: parse_number(phase)?,
+        value: parse_number(value)?,
+        interpolation,
+    })
+}
+
+// Parse a step of a sequence written as its level, followed by `glide` if it glides.
+fn parse_step(s: &str) -> Option<Step> {
+    let (level, glide) = split_word(s);
+    let glide = match &glide.to_lowercase()[..] {
+        "" => false,
+        "glide" => true,
+        _ => return None,
+    };
+    Some(Step::new(parse_number(level)?, glide))
+}
+
+// Load a wavetable from its name, followed by its scan position and interpolation if given.
+fn parse_wavetable(name: &str) -> Result<Signal, ParseSignalError> {
+    let (name, args) = match split_call(name) {
+        Some((name, args)) => (name, Some(split_args(args))),
+        None => (name, None),
+    };
+    let mut table = Wavetable::load_from_assets(name)?;
+    if let Some(args) = args {
+        let invalid = || ParseSignalError::InvalidArguments(format!("wavetable.{}", name));
+        let (position, interpolation) = match args[..] {
+            [position, interpolation] => (position, interpolation.to_lowercase()),
+            _ => return Err(invalid()),
+        };
+        table.position = parse_number(position).ok_or_else(invalid)?;
+        table.interpolation = match &interpolation[..] {
+            "linear" => wavetable::Interpolation::Linear,
+            "cubic" => wavetable::Interpolation::Cubic,
+            _ => return Err(invalid()),
+        };
+    }
+    Ok(Signal::Wavetable(table))
+}
+
+fn parse_combine(name: &str, args: &str) -> Result<Signal, ParseSignalError> {
+    if name == "cubic-bezier" {
+        let invalid = || ParseSignalError::InvalidArguments(name.to_string());
+        return parse_bezier(args).map(Signal::Ease).ok_or_else(invalid);
+    }
+
+    let args = split_args(args);
+    let invalid = || ParseSignalError::InvalidArguments(name.to_string());
+    let signal = |i: usize| -> Result<Box<Signal>, ParseSignalError> {
+        let arg = args.get(i).ok_or_else(invalid)?;
+        Ok(Box::new(arg.parse()?))
+    };
+    let number = |i: usize| -> Result<f32, ParseSignalError> {
+        let arg = args.get(i).ok_or_else(invalid)?;
+        parse_number(arg).ok_or_else(invalid)
+    };
+    let list = || -> Result<Vec<Signal>, ParseSignalError> {
+        args.iter().map(|arg| arg.parse()).collect()
+    };
+    let expect_args = |n: usize| match args.len() == n {
+        true => Ok(()),
+        false => Err(invalid()),
+    };
+
+    let combine = match name {
+        "sum" => Combine::Sum(list()?),
+        "product" => Combine::Product(list()?),
+        "min" => Combine::Min(list()?),
+        "max" => Combine::Max(list()?),
+        "scale" => {
+            expect_args(3)?;
+            Combine::Scale {
+                signal: signal(0)?,
+                gain: number(1)?,
+                bias: number(2)?,
+            }
+        }
+        "crossfade" => {
+            expect_args(3)?;
+            Combine::Crossfade {
+                a: signal(0)?,
+                b: signal(1)?,
+                mix: number(2)?,
+            }
+        }
+        "phase_mod" => {
+            expect_args(3)?;
+            Combine::PhaseMod {
+                carrier: signal(0)?,
+                modulator: signal(1)?,
+                depth: number(2)?,
+            }
+        }
+        _ => return Err(ParseSignalError::Unknown(name.to_string())),
+    };
+    Ok(Signal::Combine(combine))
+}
+
+impl From<formula::ParseError> for ParseSignalError {
+    fn from(err: formula::ParseError) -> Self {
+        ParseSignalError::Formula(err)
+    }
+}
+
+impl From<wavetable::LoadError> for ParseSignalError {
+    fn from(err: wavetable::LoadError) -> Self {
+        ParseSignalError::Wavetable(err)
+    }
+}
+
+impl fmt::Display for ParseSignalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseSignalError::Unknown(name) => write!(f, "unknown signal '{}'", name),
+            ParseSignalError::InvalidArguments(name) => {
+                write!(f, "invalid arguments for '{}'", name)
+            }
+            ParseSignalError::Formula(err) => write!(f, "invalid formula: {}", err),
+            ParseSignalError::Wavetable(err) => write!(f, "failed to load wavetable: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseSignalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(signal: &Signal) {
+        let name = signal.to_string();
+        match name.parse::<Signal>() {
+            Ok(parsed) => assert_eq!(&parsed, signal, "{} read back differently", name),
+            Err(err) => panic!("{} failed to parse: {}", name, err),
+        }
+    }
+
+    #[test]
+    fn built_in_signals_round_trip() {
+        for signal in signals::ALL.iter().chain(&signals::combinations()) {
+            assert_round_trips(signal);
+        }
+    }
+
+    #[test]
+    fn built_in_names_are_unique() {
+        // `QUART_IN` was once a second `QuadIn`, which hid the quart easing from the list.
+        assert_eq!(Signal::QUART_IN, Signal::Ease(EasingType::QuartIn));
+        for (i, a) in signals::ALL.iter().enumerate() {
+            for b in &signals::ALL[i + 1..] {
+                assert_ne!(a.to_string(), b.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let curve = Curve::new(vec![
+            Breakpoint {
+                phase: 0.0,
+                value: -1.0,
+                interpolation: Interpolation::Ease(EasingType::ElasticOut {
+                    amplitude: 1.5,
+                    period: 0.25,
+                }),
+            },
+            Breakpoint {
+                phase: 0.3,
+                value: 0.7,
+                interpolation: Interpolation::Ease(EasingType::SineInOut),
+            },
+            Breakpoint {
+                phase: 0.6,
+                value: 0.1,
+                interpolation: Interpolation::Step,
+            },
+        ]);
+        let sequencer = Sequencer {
+            steps: vec![
+                Step::new(0.25, false),
+                Step::new(-1.0, true),
+                Step::new(0.5, false),
+            ]
+            .into(),
+        };
+        let signals = vec![
+            Signal::Lfo(LfoType::Square { pulse_width: 0.25 }),
+            Signal::Lfo(LfoType::PerlinNoise {
+                seed: u32::MAX,
+                steps: 7,
+            }),
+            Signal::Ease(EasingType::BackIn { overshoot: 2.5 }),
+            Signal::Ease(EasingType::ElasticOut {
+                amplitude: 1.2,
+                period: 0.1,
+            }),
+            Signal::Ease(EasingType::PowerInOut { exponent: 3.3 }),
+            Signal::Ease(EasingType::CubicBezier {
+                x1: 0.1,
+                y1: -0.6,
+                x2: 0.7,
+                y2: 1.4,
+            }),
+            Signal::Curve(curve),
+            Signal::Rhythm(Rhythm::Euclidean {
+                pulses: 5,
+                steps: 13,
+                rotation: 2,
+                gate: 0.3,
+            }),
+            Signal::Rhythm(Rhythm::Sequencer(sequencer)),
+            Signal::Formula(Formula::parse("sin(p * 2) * 0.5").unwrap()),
+        ];
+        for signal in &signals {
+            assert_round_trips(signal);
+        }
+
+        // Parameterised signals nested within combinations
+        assert_round_trips(&Signal::Combine(Combine::Crossfade {
+            a: Box::new(signals[0].clone()),
+            b: Box::new(Signal::Combine(Combine::Sum(signals[5..].to_vec()))),
+            mix: 0.4,
+        }));
+    }
+
+    #[test]
+    fn prefixes_ignore_case() {
+        let formula = "Formula:sin(p)".parse::<Signal>().unwrap();
+        assert_eq!(formula.to_string(), "formula:sin(p)");
+        assert!(matches!(
+            "WAVETABLE.missing".parse::<Signal>(),
+            Err(ParseSignalError::Wavetable(_))
+        ));
+    }
+
+    #[test]
+    fn numbers_must_be_finite() {
+        let names = [
+            "lfo.square(NaN)",
+            "ease.back_in(inf)",
+            "ease.cubic_bezier(0.1, -inf, 0.7, 1.4)",
+            "curve(0 NaN linear)",
+            "rhythm.euclidean(5, 13, 2, NaN)",
+            "rhythm.sequencer(inf)",
+            "scale(sine, inf, 0)",
+        ];
+        for name in &names {
+            assert!(name.parse::<Signal>().is_err(), "{} should not parse", name);
+            let finite = name.replace("NaN", "0.5").replace("inf", "0.5");
+            assert!(finite.parse::<Signal>().is_ok(), "{} should parse", finite);
+        }
+    }
+}
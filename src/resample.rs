@@ -0,0 +1,147 @@
+// Resampling module
+//
+// Changing the number of oscillators would otherwise start the new ones from nothing and jump
+// the rest to new positions. Per-oscillator state is resampled to the new count by position
+// along the strip instead, and the outputs crossfade from the old values to the new ones.
+use std::time::Duration;
+
+/// How many seconds the outputs take to crossfade after the count changes.
+pub const CROSSFADE_TIME: f32 = 0.25;
+
+/// Crossfades the outputs whenever their count changes.
+pub struct Crossfade {
+    // The outputs from the previous update
+    last: Vec<f32>,
+    // The previous outputs resampled to the new count, to fade from
+    from: Vec<f32>,
+    // How far through the crossfade we are, 0..1, or 1 when there is none
+    progress: f32,
+}
+
+/// Resample the values to `size`, interpolating between the two nearest by position along the
+/// strip.
+pub fn resample(values: &[f32], size: usize) -> Vec<f32> {
+    if values.is_empty() {
+        return vec![0.0; size];
+    }
+    if values.len() == size {
+        return values.to_vec();
+    }
+    (0..size)
+        .map(|i| {
+            let x = position(i, size) * (values.len() - 1) as f32;
+            let j = (x.floor() as usize).min(values.len() - 1);
+            let next = values[(j + 1).min(values.len() - 1)];
+            values[j] + (next - values[j]) * (x - j as f32)
+        })
+        .collect()
+}
+
+/// The index of the value nearest by position along the strip to the `i`th of `size`, for
+/// state that can't be interpolated such as wrapped phases.
+pub fn nearest(i: usize, size: usize, len: usize) -> usize {
+    let x = position(i, size) * len.max(1).saturating_sub(1) as f32;
+    (x.round() as usize).min(len.max(1) - 1)
+}
+
+// The position of the `i`th of `size` values along the strip, 0..1
+fn position(i: usize, size: usize) -> f32 {
+    match size {
+        0 | 1 => 0.0,
+        _ => i as f32 / (size - 1) as f32,
+    }
+}
+
+impl Crossfade {
+    pub fn new() -> Self {
+        Crossfade {
+            last: Vec::new(),
+            from: Vec::new(),
+            progress: 1.0,
+        }
+    }
+
+    /// Fade the values in from the previous outputs if their count has changed, or carry on
+    /// with a fade in progress. `dt` is the time since the values were last processed.
+    pub fn process(&mut self, values: &mut [f32], dt: Duration) {
+        if values.len() != self.last.len() && !self.last.is_empty() {
+            self.from = resample(&self.last, values.len());
+            self.progress = 0.0;
+        }
+        if self.progress < 1.0 {
+            self.progress += dt.as_secs_f64() as f32 / CROSSFADE_TIME;
+            let progress = self.progress.min(1.0);
+            for (v, from) in values.iter_mut().zip(&self.from) {
+                *v = from + (*v - from) * progress;
+            }
+        }
+        self.last.clear();
+        self.last.extend_from_slice(values);
+    }
+}
+
+impl Default for Crossfade {
+    fn default() -> Self {
+        Crossfade::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(values: &[f32], expected: &[f32]) {
+        assert_eq!(values.len(), expected.len(), "{:?}", values);
+        for (v, e) in values.iter().zip(expected) {
+            assert!((v - e).abs() < 1e-6, "{:?} != {:?}", values, expected);
+        }
+    }
+
+    #[test]
+    fn growing_interpolates_between_neighbours() {
+        assert_close(&resample(&[0.0, 1.0], 5), &[0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(nearest(3, 5, 2), 1);
+    }
+
+    #[test]
+    fn shrinking_keeps_the_ends() {
+        assert_close(&resample(&[0.0, 1.0, 2.0, 3.0, 4.0], 3), &[0.0, 2.0, 4.0]);
+        assert_eq!(nearest(1, 3, 5), 2);
+    }
+
+    #[test]
+    fn empty_values_resample_to_nothing() {
+        assert_close(&resample(&[], 3), &[0.0; 3]);
+        assert!(resample(&[1.0, 2.0], 0).is_empty());
+        assert_eq!(nearest(2, 3, 0), 0);
+
+        // Nothing to fade from, so the first values are left alone.
+        let mut crossfade = Crossfade::new();
+        let mut values = [1.0; 4];
+        crossfade.process(&mut values, Duration::from_millis(50));
+        assert_close(&values, &[1.0; 4]);
+    }
+
+    #[test]
+    fn count_change_mid_fade_carries_on_from_the_output() {
+        let frame = Duration::from_millis(50);
+        let mut crossfade = Crossfade::new();
+        crossfade.process(&mut [0.0; 4], frame);
+
+        // A fifth of the way from the old values to the new.
+        let mut values = [1.0; 8];
+        crossfade.process(&mut values, frame);
+        assert_close(&values, &[0.2; 8]);
+
+        // The next fade starts from where the last one had reached.
+        let mut values = [1.0; 2];
+        crossfade.process(&mut values, frame);
+        assert_close(&values, &[0.36; 2]);
+
+        for _ in 0..5 {
+            values = [1.0; 2];
+            crossfade.process(&mut values, frame);
+        }
+        assert_close(&values, &[1.0; 2]);
+    }
+}
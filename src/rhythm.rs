@@ -0,0 +1,178 @@
+// Rhythmic gate signals that step with the shm phase
+//
+// Each cycle of the phase is divided into steps. Euclidean rhythms spread their pulses as evenly
+// as possible over the steps, while the sequencer plays back a level for each step.
+use nannou::math::fmod;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// The fraction of each step that a Euclidean pulse is high for by default.
+pub const DEFAULT_GATE: f32 = 0.5;
+
+/// The step lengths the sequencer can be switched between.
+pub const SEQUENCER_LENGTHS: &[usize] = &[16, 32];
+
+/// The steps of the default sequence, a rising pattern that glides back down to the start.
+pub const DEFAULT_SEQUENCE: &[Step] = &[
+    Step::new(1.0, false),
+    Step::new(0.0, false),
+    Step::new(0.25, false),
+    Step::new(0.0, false),
+    Step::new(0.5, false),
+    Step::new(0.0, false),
+    Step::new(0.75, false),
+    Step::new(0.0, false),
+    Step::new(1.0, false),
+    Step::new(0.5, false),
+    Step::new(0.75, false),
+    Step::new(0.25, false),
+    Step::new(1.0, true),
+    Step::new(0.75, true),
+    Step::new(0.5, true),
+    Step::new(0.25, true),
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Rhythm {
+    // `pulses` gates spread evenly over `steps`, shifted later by `rotation` steps
+    Euclidean {
+        pulses: u32,
+        steps: u32,
+        rotation: u32,
+        // The fraction of each step the gate is high for
+        gate: f32,
+    },
+    Sequencer(Sequencer),
+}
+
+/// A step sequencer playing one level per step.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sequencer {
+    pub steps: Cow<'static, [Step]>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    // The level of the step, 0..1
+    pub level: f32,
+    // Slide from this step's level to the next one over the length of the step
+    pub glide: bool,
+}
+
+impl Rhythm {
+    pub const EUCLIDEAN: Self = Rhythm::Euclidean {
+        pulses: 3,
+        steps: 8,
+        rotation: 0,
+        gate: DEFAULT_GATE,
+    };
+    pub const SEQUENCER: Self = Rhythm::Sequencer(Sequencer {
+        steps: Cow::Borrowed(DEFAULT_SEQUENCE),
+    });
+
+    /// The stable name of the rhythm without its parameters, e.g. `rhythm.euclidean`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rhythm::Euclidean { .. } => "rhythm.euclidean",
+            Rhythm::Sequencer(_) => "rhythm.sequencer",
+        }
+    }
+
+    pub fn amp(&self, phase: f32) -> f32 {
+        match self {
+            Rhythm::Euclidean {
+                pulses,
+                steps,
+                rotation,
+                gate,
+            } => euclidean(fmod(phase, 1.0), *pulses, *steps, *rotation, *gate),
+            Rhythm::Sequencer(sequencer) => sequencer.amp(phase),
+        }
+    }
+}
+
+// A gate that is high for the first `gate` of each step with a pulse on it. The pulses are placed
+// where the running total of `pulses / steps` crosses a whole number, which is the same pattern
+// as Bjorklund's algorithm up to rotation.
+fn euclidean(phase: f32, pulses: u32, steps: u32, rotation: u32, gate: f32) -> f32 {
+    let steps = steps.max(1);
+    let x = phase * steps as f32;
+    let step = (x.floor() as u32).min(steps - 1);
+    // Counted in u64, as the products overflow u32 for the larger step counts a name can give.
+    let (n, k) = (steps as u64, pulses.min(steps) as u64);
+    let i = (step as u64 + n - rotation as u64 % n) % n;
+    let pulse = (i * k) % n < k;
+    let t = x - step as f32;
+    if pulse && t < gate {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+impl Sequencer {
+    pub fn amp(&self, phase: f32) -> f32 {
+        let steps = &self.steps[..];
+        if steps.is_empty() {
+            return 0.0;
+        }
+        let x = fmod(phase, 1.0) * steps.len() as f32;
+        let i = (x.floor() as usize).min(steps.len() - 1);
+        let step = steps[i];
+        let level = if step.glide {
+            let next = steps[(i + 1) % steps.len()].level;
+            step.level + (next - step.level) * (x - i as f32)
+        } else {
+            step.level
+        };
+        level * 2.0 - 1.0
+    }
+
+    /// Change the number of steps, repeating the existing pattern to fill any new steps.
+    pub fn set_len(&mut self, len: usize) {
+        if len == self.steps.len() || self.steps.is_empty() {
+            return;
+        }
+        let steps: Vec<Step> = self.steps.iter().cycle().take(len).cloned().collect();
+        self.steps = Cow::Owned(steps);
+    }
+}
+
+impl Step {
+    pub const fn new(level: f32, glide: bool) -> Self {
+        Step { level, glide }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pulses: u32, steps: u32, rotation: u32) -> Vec<bool> {
+        let phase = |i: u32| i as f32 / steps as f32;
+        (0..steps)
+            .map(|i| euclidean(phase(i), pulses, steps, rotation, DEFAULT_GATE) > 0.0)
+            .collect()
+    }
+
+    #[test]
+    fn euclidean_spreads_its_pulses() {
+        let x = true;
+        let o = false;
+        assert_eq!(pattern(3, 8, 0), [x, o, o, x, o, o, x, o]);
+        assert_eq!(pattern(3, 8, 1), [o, x, o, o, x, o, o, x]);
+        assert_eq!(pattern(5, 4, 0), [x; 4]);
+    }
+
+    #[test]
+    fn euclidean_handles_large_counts() {
+        let rhythm = Rhythm::Euclidean {
+            pulses: 70_000,
+            steps: 70_000,
+            rotation: u32::MAX,
+            gate: DEFAULT_GATE,
+        };
+        assert_eq!(rhythm.amp(0.5), 1.0);
+        assert_eq!(euclidean(0.99, u32::MAX, u32::MAX, u32::MAX, 1.0), 1.0);
+    }
+}
@@ -0,0 +1,199 @@
+// Waveshaping applied to the shm phases before they are sent to the outputs
+//
+// A `Chain` is an ordered list of stages, each of which shapes every phase in turn. The phases
+// are in the range -1..1 on the way in, and most shapers keep them there.
+use crate::resample;
+use nannou::math::map_range;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// One of each shaper with its default parameters, in the order they are listed in the GUI.
+pub const ALL: &[Shaper] = &[
+    Shaper::Fold { drive: 2.0 },
+    Shaper::HardClip { threshold: 0.5 },
+    Shaper::SoftClip { drive: 2.0 },
+    Shaper::Quantise { steps: 8 },
+    Shaper::HalfRectify,
+    Shaper::FullRectify,
+    Shaper::Bias { amount: 0.0 },
+    Shaper::Gain { amount: 1.0 },
+    Shaper::Slew { rate: 3.0 },
+    Shaper::Pow { exponent: 1.0 },
+    Shaper::Range {
+        min: -1.0,
+        max: 1.0,
+    },
+    Shaper::Invert,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Shaper {
+    // Reflect the signal back on itself whenever the gain pushes it beyond -1..1
+    Fold { drive: f32 },
+    // Clamp the signal within -threshold..threshold
+    HardClip { threshold: f32 },
+    // Saturate the signal with tanh, normalised so that -1..1 still reaches -1..1
+    SoftClip { drive: f32 },
+    // Round the signal to the given number of evenly spaced levels
+    Quantise { steps: u32 },
+    // Remove the negative half of the signal
+    HalfRectify,
+    // Flip the negative half of the signal
+    FullRectify,
+    Bias { amount: f32 },
+    Gain { amount: f32 },
+    // Limit how far the signal may move each second
+    Slew { rate: f32 },
+    // Raise the signal to the given power within the range 0..1
+    Pow { exponent: f32 },
+    // Map -1..1 to the range min..max
+    Range { min: f32, max: f32 },
+    Invert,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stage {
+    pub shaper: Shaper,
+    pub bypass: bool,
+    // The previous output for each phase, used by the slew limiter
+    #[serde(skip)]
+    history: Vec<f32>,
+}
+
+/// An ordered list of shaping stages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Chain {
+    pub stages: Vec<Stage>,
+}
+
+impl Shaper {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shaper::Fold { .. } => "Fold",
+            Shaper::HardClip { .. } => "Hard Clip",
+            Shaper::SoftClip { .. } => "Soft Clip",
+            Shaper::Quantise { .. } => "Quantise",
+            Shaper::HalfRectify => "Half Rectify",
+            Shaper::FullRectify => "Full Rectify",
+            Shaper::Bias { .. } => "Bias",
+            Shaper::Gain { .. } => "Gain",
+            Shaper::Slew { .. } => "Slew",
+            Shaper::Pow { .. } => "Pow",
+            Shaper::Range { .. } => "Range",
+            Shaper::Invert => "Invert",
+        }
+    }
+
+    /// Shape a single value. The slew limiter depends on the previous value, so is applied
+    /// by `Stage::process` instead.
+    pub fn shape(&self, x: f32) -> f32 {
+        match *self {
+            Shaper::Fold { drive } => {
+                let x = x * drive;
+                1.0 - ((x + 1.0).rem_euclid(4.0) - 2.0).abs()
+            }
+            Shaper::HardClip { threshold } => x.max(-threshold).min(threshold),
+            Shaper::SoftClip { drive } => {
+                let drive = drive.max(0.01);
+                (x * drive).tanh() / drive.tanh()
+            }
+            Shaper::Quantise { steps } => {
+                let levels = (steps.max(2) - 1) as f32;
+                let x = map_range(x, -1.0, 1.0, 0.0, 1.0);
+                map_range((x * levels).round() / levels, 0.0, 1.0, -1.0, 1.0)
+            }
+            Shaper::HalfRectify => x.max(0.0),
+            Shaper::FullRectify => x.abs(),
+            Shaper::Bias { amount } => x + amount,
+            Shaper::Gain { amount } => x * amount,
+            Shaper::Slew { .. } => x,
+            Shaper::Pow { exponent } => {
+                let x: f32 = map_range(x, -1.0, 1.0, 0.0, 1.0);
+                map_range(x.max(0.0).powf(exponent), 0.0, 1.0, -1.0, 1.0)
+            }
+            Shaper::Range { min, max } => map_range(x, -1.0, 1.0, min, max),
+            Shaper::Invert => -x,
+        }
+    }
+}
+
+impl Stage {
+    pub fn new(shaper: Shaper) -> Self {
+        Stage {
+            shaper,
+            bypass: false,
+            history: Vec::new(),
+        }
+    }
+
+    /// Shape each of the given values in place, unless the stage is bypassed. `dt` is the time
+    /// since the values were last processed.
+    pub fn process(&mut self, values: &mut [f32], dt: Duration) {
+        if self.bypass {
+            self.history.clear();
+            return;
+        }
+        match self.shaper {
+            Shaper::Slew { rate } => {
+                let max_step = rate * dt.as_secs_f64() as f32;
+                // Resample the previous outputs whenever the number of phases changes, starting
+                // from the current values if there were none.
+                if self.history.len() != values.len() {
+                    self.history = match self.history.is_empty() {
+                        true => values.to_vec(),
+                        false => resample::resample(&self.history, values.len()),
+                    };
+                }
+                for (v, prev) in values.iter_mut().zip(self.history.iter_mut()) {
+                    *prev += (*v - *prev).max(-max_step).min(max_step);
+                    *v = *prev;
+                }
+            }
+            shaper => values.iter_mut().for_each(|v| *v = shaper.shape(*v)),
+        }
+    }
+}
+
+impl Chain {
+    /// Run the given values through each stage in order.
+    pub fn process(&mut self, values: &mut [f32], dt: Duration) {
+        for stage in self.stages.iter_mut() {
+            stage.process(values, dt);
+        }
+    }
+
+    pub fn push(&mut self, shaper: Shaper) {
+        self.stages.push(Stage::new(shaper));
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.stages.remove(index);
+    }
+
+    /// Swap the stage at the given index with the one before it.
+    pub fn move_up(&mut self, index: usize) {
+        if index > 0 && index < self.stages.len() {
+            self.stages.swap(index - 1, index);
+        }
+    }
+}
+
+impl Default for Chain {
+    /// The pow, invert and amplitude range that the phases have always been shaped by, with
+    /// the invert bypassed.
+    fn default() -> Self {
+        let mut invert = Stage::new(Shaper::Invert);
+        invert.bypass = true;
+        Chain {
+            stages: vec![
+                Stage::new(Shaper::Pow { exponent: 1.0 }),
+                invert,
+                Stage::new(Shaper::Range {
+                    min: -1.0,
+                    max: 1.0,
+                }),
+            ],
+        }
+    }
+}
@@ -1,26 +1,80 @@
 // Simple Harmonic Motion module
-use crate::signals::Signal;
+use crate::grid::Grid;
+use crate::resample;
+use crate::signals::{Combine, LfoType, Signal};
+use crate::spread::Spread;
+use crate::symmetry::Symmetry;
+use crate::transport::Transport;
 use nannou::math::fmod;
+use std::time::Duration;
 
 pub struct Shm {
     // LFO or the fract component of an Easing Curve
     signal_type: Signal,
     // Our vector of oscillator phases per SHM block
     phases: Vec<f32>,
-    // The master frequency
-    start_angle: f32,
     // Defines the phase offsets between oscillators
     pub a_velocity: f32,
-    // Defines the specific offset pattern, set by offset_hz
-    angle_offset: f32,
-    // Master Speed of all osciallators
+    // Master Speed of all osciallators in cycles per second
     pub hz: f32,
-    // The rate at which oscillators fall in and out of phase
+    // The rate at which oscillators fall in and out of phase, in cycles per second of offset
+    // between neighbouring oscillators
     pub offset_hz: f32,
+    // The time the motion is played at
+    pub transport: Transport,
+    // Where the motion has reached since the speeds last changed
+    anchor: Anchor,
     // Skew the waveform in a direction
     pub skew: f32,
-    // Defines is our phases are mirrored or not
-    pub mirror: bool,
+    // Mirrors, repeats, reverses or rotates the pattern along the strip
+    pub symmetry: Symmetry,
+    // The phases of a single segment of the symmetry
+    pattern: Vec<f32>,
+    // Lays the oscillators out in rows when set, instead of along a strip
+    grid: Option<Grid>,
+    // The number of oscillators along the strip, to return to when the grid is turned off
+    strip_size: usize,
+    // Gives each oscillator its own speed multiplier and phase offset
+    pub spread: Spread,
+    // A second signal to blend towards by the morph amount
+    morph_signal: Option<Signal>,
+    // How far to blend from the signal to the morph signal, 0..1
+    pub morph: f32,
+    // Animates the morph amount when set
+    pub morph_lfo: Option<LfoType>,
+    // Speed of the morph lfo in cycles per second
+    pub morph_lfo_hz: f32,
+    // The angle of the morph lfo at the current transport time
+    morph_lfo_angle: f32,
+    // A crossfade in progress from the previous signal to the current one
+    transition: Option<Transition>,
+    // How many seconds a crossfade takes, 0 switches signals instantly
+    pub transition_time: f32,
+}
+
+// The angles reached at a transport time, and the speeds they have moved at since. The motion
+// is a function of the transport time from here, so seeking back to a time shows the same frame
+// until one of the speeds is changed. The angles are kept in f64 and wrapped to 0..1, so that
+// the motion stays as smooth after days of running as it is at the start.
+#[derive(Clone, Debug)]
+struct Anchor {
+    time: f64,
+    start_angle: f64,
+    angle_offset: f64,
+    morph_lfo_angle: f64,
+    // The angle each oscillator has gained over the others from its spread speed
+    spread_angles: Vec<f64>,
+    hz: f32,
+    offset_hz: f32,
+    morph_lfo_hz: f32,
+    spread: Spread,
+}
+
+struct Transition {
+    // The output when the crossfade started, with its morph and any earlier crossfade frozen
+    from: Signal,
+    // How far through the crossfade we are, 0..1
+    progress: f32,
 }
 
 impl Shm {
@@ -28,20 +82,52 @@ impl Shm {
     pub fn new(size: usize, hz: f32, a_velocity: f32, offset_hz: f32) -> Self {
         let signal_type = Signal::SINE;
         let phases = vec![0.0; size];
-        let start_angle = 0.0;
-        let angle_offset = 0.0;
+        let transport = Transport::new();
         let skew = 1.0;
-        let mirror = false;
+        let symmetry = Symmetry::new();
+        let pattern = vec![0.0; size];
+        let grid = None;
+        let strip_size = size;
+        let spread = Spread::new();
+        let morph_signal = None;
+        let morph = 0.0;
+        let morph_lfo = None;
+        let morph_lfo_hz = 0.3;
+        let morph_lfo_angle = 0.0;
+        let anchor = Anchor {
+            time: 0.0,
+            start_angle: 0.0,
+            angle_offset: 0.0,
+            morph_lfo_angle: 0.0,
+            spread_angles: vec![0.0; size],
+            hz,
+            offset_hz,
+            morph_lfo_hz,
+            spread,
+        };
+        let transition = None;
+        let transition_time = 0.0;
         Shm {
             signal_type,
             phases,
             hz,
-            start_angle,
-            angle_offset,
             a_velocity,
             offset_hz,
+            transport,
+            anchor,
             skew,
-            mirror,
+            symmetry,
+            pattern,
+            grid,
+            strip_size,
+            spread,
+            morph_signal,
+            morph,
+            morph_lfo,
+            morph_lfo_hz,
+            morph_lfo_angle,
+            transition,
+            transition_time,
         }
     }
 
@@ -51,9 +137,125 @@ impl Shm {
         self.signal_type = signal_type;
     }
 
+    /// The signal that the shm module is currently using
+    pub fn signal_type(&self) -> &Signal {
+        &self.signal_type
+    }
+
+    /// Crossfade from the current signal to the given one over the
+    /// `transition_time`, or switch instantly if the time is 0
+    pub fn transition_to(&mut self, signal_type: Signal) {
+        self.start_transition();
+        self.signal_type = signal_type;
+    }
+
+    /// Set the signal to blend towards by the morph amount, crossfading to it over the
+    /// `transition_time`. `None` disables morphing
+    pub fn set_morph_signal(&mut self, morph_signal: Option<Signal>) {
+        self.start_transition();
+        self.morph_signal = morph_signal;
+    }
+
+    // Start a crossfade from whatever is on screen, including a crossfade part way through.
+    fn start_transition(&mut self) {
+        if self.transition_time <= 0.0 {
+            return;
+        }
+        let morph = self.morph_amount();
+        let signal = blended(&self.signal_type, self.morph_signal.as_ref(), morph);
+        let from = match self.transition.take() {
+            Some(t) => crossfade(t.from, signal, t.progress),
+            None => signal,
+        };
+        self.transition = Some(Transition {
+            from,
+            progress: 0.0,
+        });
+    }
+
+    /// The signal we are morphing towards, if any
+    pub fn morph_signal(&self) -> Option<&Signal> {
+        self.morph_signal.as_ref()
+    }
+
+    /// The current morph amount, following the morph lfo if there is one
+    pub fn morph_amount(&self) -> f32 {
+        match self.morph_lfo {
+            Some(lfo) => lfo.amp(fmod(self.morph_lfo_angle, 1.0)) * 0.5 + 0.5,
+            None => self.morph,
+        }
+    }
+
+    /// Restart the cycle from the beginning, e.g. on a downbeat
+    pub fn reset_phase(&mut self) {
+        self.set_cycle_phase(0.0);
+    }
+
+    /// Carry on from the given point in the cycle, 0..1, e.g. to line up with a tempo clock
+    pub fn set_cycle_phase(&mut self, phase: f64) {
+        let mut anchor = self.anchor_at(self.transport.time());
+        anchor.start_angle = phase;
+        for a in anchor.spread_angles.iter_mut() {
+            *a = 0.0;
+        }
+        self.anchor = anchor;
+    }
+
+    // Where the motion has reached at the given time, to carry on from at the current speeds.
+    fn anchor_at(&self, time: f64) -> Anchor {
+        let anchor = &self.anchor;
+        let size = self.phases.len();
+        // Each oscillator carries on from the nearest along the strip when the size changes.
+        let len = anchor.spread_angles.len();
+        let spread_angles = (0..size).map(|i| match len {
+            0 => 0.0,
+            _ if len == size => anchor.spread_angle(i, time),
+            _ => anchor.spread_angle(resample::nearest(i, size, len), time),
+        });
+        Anchor {
+            time,
+            start_angle: anchor.start_angle(time),
+            angle_offset: anchor.angle_offset(time),
+            morph_lfo_angle: anchor.morph_lfo_angle(time),
+            spread_angles: spread_angles.collect(),
+            hz: self.hz,
+            offset_hz: self.offset_hz,
+            morph_lfo_hz: self.morph_lfo_hz,
+            spread: self.spread,
+        }
+    }
+
+    /// Lay the oscillators out as the given grid, resizing to one per cell, or
+    /// along a strip for `None`, returning to the count the strip had before the grid
+    pub fn set_grid(&mut self, grid: Option<Grid>) {
+        match grid {
+            Some(ref grid) => {
+                self.strip_size = self.strip_size();
+                self.phases.resize(grid.size(), 0.0);
+            }
+            None => self.phases.resize(self.strip_size(), 0.0),
+        }
+        self.grid = grid;
+    }
+
+    /// The number of oscillators along the strip, or that the strip will have once the grid is
+    /// turned off
+    pub fn strip_size(&self) -> usize {
+        match self.grid {
+            Some(_) => self.strip_size,
+            None => self.phases.len(),
+        }
+    }
+
+    /// The grid the oscillators are laid out in, if any
+    pub fn grid(&self) -> Option<&Grid> {
+        self.grid.as_ref()
+    }
+
     /// Set the number or signals
     pub fn set_size(&mut self, size: usize) {
-        self.phases.resize(size, 0.0);
+        // Resampled by position along the strip, so the phases don't jump before the next update.
+        self.phases = resample::resample(&self.phases, size);
     }
 
     /// Get the size of the shm vector
@@ -67,27 +269,280 @@ impl Shm {
         &self.phases
     }
 
-    pub fn update(&mut self) {
-        self.start_angle += self.hz * 0.05;
-        let mut angle = self.start_angle;
+    /// Advance the transport by the time elapsed since the last update and find the phases
+    /// at its new time
+    pub fn update(&mut self, dt: Duration) {
+        self.transport.advance(dt);
+        let time = self.transport.time();
+
+        // Carry on from where the motion has reached whenever a speed changes.
+        let anchor = &self.anchor;
+        let size = self.phases.len();
+        if anchor.hz != self.hz
+            || anchor.offset_hz != self.offset_hz
+            || anchor.morph_lfo_hz != self.morph_lfo_hz
+            || anchor.spread != self.spread
+            || anchor.spread_angles.len() != size
+        {
+            self.anchor = self.anchor_at(time);
+        }
+
+        let mut angle = self.anchor.start_angle(time) as f32;
+        let angle_offset = self.anchor.angle_offset(time);
+        self.morph_lfo_angle = self.anchor.morph_lfo_angle(time) as f32;
+        let morph = self.morph_amount();
+
+        // The crossfade runs in real time, even while the transport is paused.
+        let dt = dt.as_secs_f64() as f32;
 
-        if self.mirror {
-            let half_size = self.size() as f32 / 2.0;
-            let (first, last) = self.phases.split_at_mut(half_size.round() as usize);
+        // Step through any crossfade, finishing it once we reach the new signal.
+        if let Some(ref mut transition) = self.transition {
+            transition.progress += dt / self.transition_time.max(f32::EPSILON);
+        }
+        if matches!(self.transition, Some(ref t) if t.progress >= 1.0) {
+            self.transition = None;
+        }
+
+        let signal_type = &self.signal_type;
+        let morph_signal = self.morph_signal.as_ref();
+        let transition = self.transition.as_ref();
+        let amp = |phase: f32| {
+            let amp = blend(signal_type, morph_signal, morph, phase);
+            match transition {
+                Some(t) => {
+                    let from = t.from.amp(phase);
+                    from + (amp - from) * t.progress
+                }
+                None => amp,
+            }
+        };
+
+        // The angle each oscillator has gained from the speed spread, plus its phase spread.
+        let spread = self.spread;
+        let anchor = &self.anchor;
+        let spread_angle =
+            |i: usize| anchor.spread_angle(i, time) as f32 + spread.phase_at(i, size);
 
-            for p in first.iter_mut() {
-                *p = self.signal_type.amp(fmod(angle, 1.0).powf(self.skew));
-                angle += self.a_velocity + self.angle_offset;
-                self.angle_offset += self.offset_hz * 0.00005;
+        let symmetry = self.symmetry;
+        if let Some(ref grid) = self.grid {
+            // Each cell is offset by its place in the grid. Only the cells of the first segment
+            // of each row are computed, then the symmetry folds them across the rest of the row.
+            let len = symmetry.pattern_len(grid.width);
+            self.pattern.resize(len * grid.height, 0.0);
+            for (j, p) in self.pattern.iter_mut().enumerate() {
+                let (x, y) = (j % len, j / len);
+                let i = y * grid.width + x;
+                // Wrapped in f64, as the drift of the far cells is too large to hold in f32.
+                let drift = wrap(angle_offset * i as f64) as f32;
+                let angle = angle + grid.offset(x, y, self.a_velocity) + drift + spread_angle(i);
+                *p = amp(fmod(angle, 1.0).powf(self.skew));
             }
-            for (l, f) in last.iter_mut().rev().zip(first) {
-                *l = *f;
+            let pattern = &self.pattern;
+            for (i, p) in self.phases.iter_mut().enumerate() {
+                let (x, y) = grid.cell(i);
+                *p = pattern[y * len + symmetry.index(x, grid.width, len)];
             }
         } else {
-            for p in self.phases.iter_mut() {
-                *p = self.signal_type.amp(fmod(angle, 1.0).powf(self.skew));
-                angle += self.a_velocity + self.angle_offset;
-                self.angle_offset += self.offset_hz * 0.00005;
+            // Compute a single segment, then read each position along the strip from it.
+            let len = symmetry.pattern_len(size);
+            self.pattern.resize(len, 0.0);
+            for (i, p) in self.pattern.iter_mut().enumerate() {
+                *p = amp(fmod(angle + spread_angle(i), 1.0).powf(self.skew));
+                angle += self.a_velocity + angle_offset as f32;
+            }
+            let pattern = &self.pattern;
+            for (i, p) in self.phases.iter_mut().enumerate() {
+                *p = pattern[symmetry.index(i, size, len)];
+            }
+        }
+    }
+}
+
+// Each of the angles at the given time, wrapped to 0..1. As the oscillators are offset by whole
+// multiples of `angle_offset`, it can be wrapped along with the others.
+impl Anchor {
+    fn start_angle(&self, time: f64) -> f64 {
+        wrap(self.start_angle + self.hz as f64 * (time - self.time))
+    }
+
+    fn angle_offset(&self, time: f64) -> f64 {
+        wrap(self.angle_offset + self.offset_hz as f64 * (time - self.time))
+    }
+
+    fn morph_lfo_angle(&self, time: f64) -> f64 {
+        wrap(self.morph_lfo_angle + self.morph_lfo_hz as f64 * (time - self.time))
+    }
+
+    fn spread_angle(&self, i: usize, time: f64) -> f64 {
+        let size = self.spread_angles.len();
+        let speed = self.hz as f64 * (self.spread.speed_at(i, size) as f64 - 1.0);
+        wrap(self.spread_angles[i] + speed * (time - self.time))
+    }
+}
+
+// Wrap an angle in cycles into 0..1.
+fn wrap(angle: f64) -> f64 {
+    angle - angle.floor()
+}
+
+// Blend from one signal to an optional second signal by the given amount.
+fn blend(a: &Signal, b: Option<&Signal>, amount: f32, phase: f32) -> f32 {
+    let a_amp = a.amp(phase);
+    match b {
+        Some(b) => a_amp + (b.amp(phase) - a_amp) * amount,
+        None => a_amp,
+    }
+}
+
+// The signal blended from one signal to an optional second by the given amount.
+fn blended(a: &Signal, b: Option<&Signal>, amount: f32) -> Signal {
+    match b {
+        Some(b) => crossfade(a.clone(), b.clone(), amount),
+        None => a.clone(),
+    }
+}
+
+fn crossfade(a: Signal, b: Signal, mix: f32) -> Signal {
+    Signal::Combine(Combine::Crossfade {
+        a: Box::new(a),
+        b: Box::new(b),
+        mix,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three days of updates at 60 frames per second.
+    const SOAK_FRAMES: u32 = 3 * 24 * 60 * 60 * 60;
+    const FRAMES_PER_MINUTE: u32 = 60 * 60;
+    // The frames checked after seeking to the end of the soak.
+    const SEEK_FRAMES: u32 = 10;
+
+    // The phase of each oscillator worked out directly in f64, for a shm running its default
+    // sine with no symmetry.
+    fn expected(shm: &Shm, time: f64) -> Vec<f32> {
+        let size = shm.size();
+        let hz = shm.hz as f64;
+        (0..size)
+            .map(|i| {
+                let place = match shm.grid() {
+                    Some(grid) => {
+                        let (x, y) = grid.cell(i);
+                        grid.offset(x, y, shm.a_velocity) as f64
+                    }
+                    None => shm.a_velocity as f64 * i as f64,
+                };
+                let drift = shm.offset_hz as f64 * time * i as f64;
+                let speed = hz * (shm.spread.speed_at(i, size) as f64 - 1.0);
+                let spread = speed * time + shm.spread.phase_at(i, size) as f64;
+                let angle = hz * time + place + drift + spread;
+                Signal::SINE.amp(wrap(angle) as f32)
+            })
+            .collect()
+    }
+
+    // Settle the shm at the start, then seek to the end of the soak and check a few frames
+    // against the phases worked out directly.
+    fn check_after_soak(mut shm: Shm) {
+        let frame = Duration::from_secs(1) / 60;
+        shm.update(Duration::from_secs(0));
+        shm.transport.seek(frame.as_secs_f64() * SOAK_FRAMES as f64);
+        for n in 1..=SEEK_FRAMES {
+            shm.update(frame);
+            let expected = expected(&shm, shm.transport.time());
+            for (i, (phase, expected)) in shm.phases().iter().zip(expected).enumerate() {
+                let error = (phase - expected).abs();
+                assert!(error < 1e-4, "oscillator {} drifted at frame {}", i, n);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_keeps_the_strip_size() {
+        let mut shm = Shm::new(100, 0.3, 0.005, 0.0);
+        shm.set_grid(Some(Grid::new(8, 4)));
+        assert_eq!(shm.size(), 32);
+        shm.set_grid(Some(Grid::new(16, 4)));
+        assert_eq!((shm.size(), shm.strip_size()), (64, 100));
+        shm.set_grid(None);
+        assert_eq!(shm.size(), 100);
+    }
+
+    #[test]
+    fn grid_rows_are_reflected() {
+        let mut shm = Shm::new(8, 0.3, 0.05, 0.2);
+        let mut grid = Grid::new(9, 3);
+        grid.offset = crate::grid::Offset::Radial;
+        grid.centre = [0.25, 0.0];
+        shm.set_grid(Some(grid));
+        shm.symmetry.segments = 2;
+        shm.update(Duration::from_millis(700));
+        for row in shm.phases().chunks(9) {
+            for x in 0..9 {
+                assert_eq!(row[x], row[8 - x], "{:?}", row);
+            }
+        }
+    }
+
+    #[test]
+    fn seeking_back_finds_the_same_phases() {
+        let mut shm = Shm::new(16, 0.37, 0.01, 0.02);
+        shm.spread.speed = 0.3;
+        let frame = Duration::from_secs(1) / 60;
+        for _ in 0..90 {
+            shm.update(frame);
+        }
+        let time = shm.transport.time();
+        let phases = shm.phases().to_vec();
+        for _ in 0..240 {
+            shm.update(frame);
+        }
+        shm.transport.seek(time);
+        shm.update(Duration::from_secs(0));
+        assert_eq!(shm.phases(), &phases[..]);
+    }
+
+    #[test]
+    fn strip_does_not_drift() {
+        check_after_soak(Shm::new(8, 0.37, 0.01, 0.001));
+    }
+
+    #[test]
+    fn spread_does_not_drift() {
+        let mut shm = Shm::new(64, 0.37, 0.01, 0.001);
+        shm.spread.distribution = crate::spread::Distribution::Random { seed: 7 };
+        shm.spread.speed = 0.3;
+        shm.spread.phase = 0.25;
+        check_after_soak(shm);
+    }
+
+    #[test]
+    fn grid_does_not_drift() {
+        let mut shm = Shm::new(8, 0.37, 0.01, 0.001);
+        shm.set_grid(Some(Grid::new(64, 32)));
+        check_after_soak(shm);
+    }
+
+    // Run the shm for days of frames and check that the phases never drift from where they
+    // should be, and that the transport time keeps up with the frames. This takes a while, so
+    // run it with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn soak() {
+        let mut shm = Shm::new(8, 0.37, 0.01, 0.001);
+        let frame = Duration::from_secs(1) / 60;
+        for n in 1..=SOAK_FRAMES {
+            shm.update(frame);
+            if n % FRAMES_PER_MINUTE == 0 {
+                let time = shm.transport.time();
+                let error = (time - frame.as_secs_f64() * n as f64).abs();
+                assert!(error < 1e-6, "time drifted at frame {}", n);
+                for (phase, expected) in shm.phases().iter().zip(expected(&shm, time)) {
+                    let error = (phase - expected).abs();
+                    assert!(error < 1e-4, "phase drifted at frame {}", n);
+                }
             }
         }
     }
@@ -1,15 +1,24 @@
+use crate::formula::Formula;
+use crate::rhythm::Rhythm;
+use crate::wavetable::Wavetable;
 use nannou::ease::*;
 use nannou::math::fmod;
 use nannou::prelude::*;
-use nannou::rand::random_f32;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 
-pub const ALL: &'static [Signal] = &[
+pub const ALL: &[Signal] = &[
     Signal::SINE,
     Signal::TRIANGLE,
     Signal::SAWTOOTH,
     Signal::SQUARE,
-    Signal::NOISE,
+    Signal::VALUE_NOISE,
+    Signal::PERLIN_NOISE,
+    Signal::SAMPLE_AND_HOLD,
+    Signal::RANDOM_WALK,
     Signal::BACK_IN,
     Signal::BACK_IN_OUT,
     Signal::BACK_IN_OUT_S,
@@ -43,27 +52,102 @@ pub const ALL: &'static [Signal] = &[
     Signal::SINE_IN,
     Signal::SINE_IN_OUT,
     Signal::SINE_OUT,
+    Signal::POWER_IN,
+    Signal::POWER_IN_OUT,
+    Signal::POWER_OUT,
+    Signal::CUBIC_BEZIER,
+    Signal::CURVE,
+    Signal::EUCLIDEAN,
+    Signal::SEQUENCER,
 ];
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// The overshoot used by the plain Back easings, matching Penner's original equations.
+pub const DEFAULT_OVERSHOOT: f32 = 1.70158;
+/// The overshoot used by the Back `_S` easings.
+pub const DEFAULT_OVERSHOOT_S: f32 = 0.8;
+/// The amplitude of the Elastic easings. Values below 1.0 are treated as 1.0.
+pub const DEFAULT_ELASTIC_AMPLITUDE: f32 = 1.0;
+/// The period of the Elastic In and Out easings.
+pub const DEFAULT_ELASTIC_PERIOD: f32 = 0.3;
+/// The period of the Elastic InOut easing.
+pub const DEFAULT_ELASTIC_IN_OUT_PERIOD: f32 = 0.45;
+/// The exponent of the Power easings.
+pub const DEFAULT_EXPONENT: f32 = 2.0;
+/// The control points `[x1, y1, x2, y2]` of the cubic bezier easing, matching CSS `ease`.
+pub const DEFAULT_BEZIER: [f32; 4] = [0.25, 0.1, 0.25, 1.0];
+/// The number of random points per cycle of the noise LFOs.
+pub const DEFAULT_NOISE_STEPS: u32 = 16;
+
+/// The breakpoints of the default `Signal::CURVE`.
+pub const DEFAULT_CURVE: &[Breakpoint] = &[
+    Breakpoint {
+        phase: 0.0,
+        value: -1.0,
+        interpolation: Interpolation::Ease(EasingType::CubicInOut),
+    },
+    Breakpoint {
+        phase: 0.5,
+        value: 1.0,
+        interpolation: Interpolation::Linear,
+    },
+    Breakpoint {
+        phase: 0.75,
+        value: 0.0,
+        interpolation: Interpolation::Step,
+    },
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Signal {
     Lfo(LfoType),
     Ease(EasingType),
+    Curve(Curve),
+    Rhythm(Rhythm),
+    Wavetable(Wavetable),
+    Combine(Combine),
+    Formula(Formula),
 }
 
 impl Signal {
     pub const SINE: Self = Signal::Lfo(LfoType::Sine);
     pub const TRIANGLE: Self = Signal::Lfo(LfoType::Triangle);
     pub const SAWTOOTH: Self = Signal::Lfo(LfoType::Sawtooth);
-    pub const SQUARE: Self = Signal::Lfo(LfoType::Square);
-    pub const NOISE: Self = Signal::Lfo(LfoType::Noise);
-
-    pub const BACK_IN: Self = Signal::Ease(EasingType::BackIn);
-    pub const BACK_IN_OUT: Self = Signal::Ease(EasingType::BackInOut);
-    pub const BACK_IN_OUT_S: Self = Signal::Ease(EasingType::BackInOutS);
-    pub const BACK_IN_S: Self = Signal::Ease(EasingType::BackInS);
-    pub const BACK_OUT: Self = Signal::Ease(EasingType::BackOut);
-    pub const BACK_OUT_S: Self = Signal::Ease(EasingType::BackOutS);
+    pub const SQUARE: Self = Signal::Lfo(LfoType::Square { pulse_width: 0.5 });
+    pub const VALUE_NOISE: Self = Signal::Lfo(LfoType::ValueNoise {
+        seed: 0,
+        steps: DEFAULT_NOISE_STEPS,
+    });
+    pub const PERLIN_NOISE: Self = Signal::Lfo(LfoType::PerlinNoise {
+        seed: 0,
+        steps: DEFAULT_NOISE_STEPS,
+    });
+    pub const SAMPLE_AND_HOLD: Self = Signal::Lfo(LfoType::SampleAndHold {
+        seed: 0,
+        steps: DEFAULT_NOISE_STEPS,
+    });
+    pub const RANDOM_WALK: Self = Signal::Lfo(LfoType::RandomWalk {
+        seed: 0,
+        steps: DEFAULT_NOISE_STEPS,
+    });
+
+    pub const BACK_IN: Self = Signal::Ease(EasingType::BackIn {
+        overshoot: DEFAULT_OVERSHOOT,
+    });
+    pub const BACK_IN_OUT: Self = Signal::Ease(EasingType::BackInOut {
+        overshoot: DEFAULT_OVERSHOOT,
+    });
+    pub const BACK_IN_OUT_S: Self = Signal::Ease(EasingType::BackInOutS {
+        overshoot: DEFAULT_OVERSHOOT_S,
+    });
+    pub const BACK_IN_S: Self = Signal::Ease(EasingType::BackInS {
+        overshoot: DEFAULT_OVERSHOOT_S,
+    });
+    pub const BACK_OUT: Self = Signal::Ease(EasingType::BackOut {
+        overshoot: DEFAULT_OVERSHOOT,
+    });
+    pub const BACK_OUT_S: Self = Signal::Ease(EasingType::BackOutS {
+        overshoot: DEFAULT_OVERSHOOT_S,
+    });
     pub const BOUNCE_IN: Self = Signal::Ease(EasingType::BounceIn);
     pub const BOUNCE_IN_OUT: Self = Signal::Ease(EasingType::BounceInOut);
     pub const BOUNCE_OUT: Self = Signal::Ease(EasingType::BounceOut);
@@ -73,16 +157,25 @@ impl Signal {
     pub const CUBIC_IN: Self = Signal::Ease(EasingType::CubicIn);
     pub const CUBIC_IN_OUT: Self = Signal::Ease(EasingType::CubicInOut);
     pub const CUBIC_OUT: Self = Signal::Ease(EasingType::CubicOut);
-    pub const ELASTIC_IN: Self = Signal::Ease(EasingType::ElasticIn);
-    pub const ELASTIC_IN_OUT: Self = Signal::Ease(EasingType::ElasticInOut);
-    pub const ELASTIC_OUT: Self = Signal::Ease(EasingType::ElasticOut);
+    pub const ELASTIC_IN: Self = Signal::Ease(EasingType::ElasticIn {
+        amplitude: DEFAULT_ELASTIC_AMPLITUDE,
+        period: DEFAULT_ELASTIC_PERIOD,
+    });
+    pub const ELASTIC_IN_OUT: Self = Signal::Ease(EasingType::ElasticInOut {
+        amplitude: DEFAULT_ELASTIC_AMPLITUDE,
+        period: DEFAULT_ELASTIC_IN_OUT_PERIOD,
+    });
+    pub const ELASTIC_OUT: Self = Signal::Ease(EasingType::ElasticOut {
+        amplitude: DEFAULT_ELASTIC_AMPLITUDE,
+        period: DEFAULT_ELASTIC_PERIOD,
+    });
     pub const EXPO_IN: Self = Signal::Ease(EasingType::ExpoIn);
     pub const EXPO_IN_OUT: Self = Signal::Ease(EasingType::ExpoInOut);
     pub const EXPO_OUT: Self = Signal::Ease(EasingType::ExpoOut);
     pub const QUAD_IN: Self = Signal::Ease(EasingType::QuadIn);
     pub const QUAD_IN_OUT: Self = Signal::Ease(EasingType::QuadInOut);
     pub const QUAD_OUT: Self = Signal::Ease(EasingType::QuadOut);
-    pub const QUART_IN: Self = Signal::Ease(EasingType::QuadIn);
+    pub const QUART_IN: Self = Signal::Ease(EasingType::QuartIn);
     pub const QUART_IN_OUT: Self = Signal::Ease(EasingType::QuartInOut);
     pub const QUART_OUT: Self = Signal::Ease(EasingType::QuartOut);
     pub const QUINT_IN: Self = Signal::Ease(EasingType::QuintIn);
@@ -91,37 +184,75 @@ impl Signal {
     pub const SINE_IN: Self = Signal::Ease(EasingType::SineIn);
     pub const SINE_IN_OUT: Self = Signal::Ease(EasingType::SineInOut);
     pub const SINE_OUT: Self = Signal::Ease(EasingType::SineOut);
+    pub const POWER_IN: Self = Signal::Ease(EasingType::PowerIn {
+        exponent: DEFAULT_EXPONENT,
+    });
+    pub const POWER_IN_OUT: Self = Signal::Ease(EasingType::PowerInOut {
+        exponent: DEFAULT_EXPONENT,
+    });
+    pub const POWER_OUT: Self = Signal::Ease(EasingType::PowerOut {
+        exponent: DEFAULT_EXPONENT,
+    });
+    pub const CUBIC_BEZIER: Self = Signal::Ease(EasingType::CubicBezier {
+        x1: DEFAULT_BEZIER[0],
+        y1: DEFAULT_BEZIER[1],
+        x2: DEFAULT_BEZIER[2],
+        y2: DEFAULT_BEZIER[3],
+    });
+
+    pub const CURVE: Self = Signal::Curve(Curve {
+        points: Cow::Borrowed(DEFAULT_CURVE),
+    });
+
+    pub const EUCLIDEAN: Self = Signal::Rhythm(Rhythm::EUCLIDEAN);
+    pub const SEQUENCER: Self = Signal::Rhythm(Rhythm::SEQUENCER);
 
     pub fn amp(&self, phase: f32) -> f32 {
         match self {
             Signal::Lfo(lfo_type) => lfo_type.amp(phase),
             Signal::Ease(ease_type) => ease_type.amp(phase),
+            Signal::Curve(curve) => curve.amp(phase),
+            Signal::Rhythm(rhythm) => rhythm.amp(phase),
+            Signal::Wavetable(table) => table.amp(phase),
+            Signal::Combine(combine) => combine.amp(phase),
+            Signal::Formula(formula) => formula.amp(phase),
         }
     }
-
-    pub fn all_names() -> Vec<String> {
-        let mut list = Vec::new();
-        for signal in ALL {
-            list.push(format!("{:?}", signal));
-        }
-        list
-    }
 }
 
 //------------------ LFO'S
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum LfoType {
     Sine,
     Triangle,
     Sawtooth,
-    Square,
-    Noise,
+    // The pulse width is the fraction of each cycle spent high, 0.5 gives a square wave
+    Square { pulse_width: f32 },
+    // The noise LFOs loop over `steps` random points per cycle, generated from `seed`
+    ValueNoise { seed: u32, steps: u32 },
+    PerlinNoise { seed: u32, steps: u32 },
+    SampleAndHold { seed: u32, steps: u32 },
+    RandomWalk { seed: u32, steps: u32 },
 }
 
 impl LfoType {
     pub fn amp(&self, phase: f32) -> f32 {
         lfo(*self, phase)
     }
+
+    /// The name of the variant without its parameters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LfoType::Sine => "Sine",
+            LfoType::Triangle => "Triangle",
+            LfoType::Sawtooth => "Sawtooth",
+            LfoType::Square { .. } => "Square",
+            LfoType::ValueNoise { .. } => "ValueNoise",
+            LfoType::PerlinNoise { .. } => "PerlinNoise",
+            LfoType::SampleAndHold { .. } => "SampleAndHold",
+            LfoType::RandomWalk { .. } => "RandomWalk",
+        }
+    }
 }
 
 pub fn lfo(lfo_type: LfoType, phase: f32) -> f32 {
@@ -129,8 +260,11 @@ pub fn lfo(lfo_type: LfoType, phase: f32) -> f32 {
         LfoType::Sine => sine(phase),
         LfoType::Triangle => triangle(phase),
         LfoType::Sawtooth => sawtooth(phase),
-        LfoType::Square => square(phase),
-        LfoType::Noise => noise(),
+        LfoType::Square { pulse_width } => square(phase, pulse_width),
+        LfoType::ValueNoise { seed, steps } => value_noise(phase, seed, steps),
+        LfoType::PerlinNoise { seed, steps } => perlin_noise(phase, seed, steps),
+        LfoType::SampleAndHold { seed, steps } => sample_and_hold(phase, seed, steps),
+        LfoType::RandomWalk { seed, steps } => random_walk(phase, seed, steps),
     }
 }
 
@@ -140,25 +274,222 @@ fn sine(phase: f32) -> f32 {
 fn triangle(phase: f32) -> f32 {
     (phase * -2.0 + 1.0).abs() * 2.0 - 1.0
 }
-fn square(phase: f32) -> f32 {
-    (if fmod(phase, 1.0) < 0.5 { -1.0 } else { 1.0 })
+fn square(phase: f32, pulse_width: f32) -> f32 {
+    if fmod(phase, 1.0) < 1.0 - pulse_width {
+        -1.0
+    } else {
+        1.0
+    }
 }
 fn sawtooth(phase: f32) -> f32 {
     fmod(phase, 1.0) * -2.0 + 1.0
 }
-fn noise() -> f32 {
-    random_f32() * 2.0 - 1.0
+
+//------------------ NOISE
+// The noise LFOs are driven by the phase rather than a random number generator, so they can be
+// slowed down with `Shm::hz` and a given seed always produces the same output. Each cycle is
+// divided into `steps` lattice points that wrap around, so the noise loops without a seam.
+
+// Hash a seed and lattice index to a value in the range -1..1.
+pub fn hash(seed: u32, index: u32) -> f32 {
+    let mut x = index.wrapping_mul(0x9E37_79B9) ^ seed.wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846C_A68B);
+    x ^= x >> 16;
+    (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+// Split the phase into a lattice index and the fractional position between it and the next.
+fn lattice(phase: f32, steps: u32) -> (u32, u32, f32) {
+    let steps = steps.max(1);
+    let x = fmod(phase, 1.0) * steps as f32;
+    let i = (x.floor() as u32).min(steps - 1);
+    (i, (i + 1) % steps, x - i as f32)
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn value_noise(phase: f32, seed: u32, steps: u32) -> f32 {
+    let (i, j, t) = lattice(phase, steps);
+    let (a, b) = (hash(seed, i), hash(seed, j));
+    a + (b - a) * smoothstep(t)
+}
+
+fn perlin_noise(phase: f32, seed: u32, steps: u32) -> f32 {
+    let (i, j, t) = lattice(phase, steps);
+    let a = hash(seed, i) * t;
+    let b = hash(seed, j) * (t - 1.0);
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    // 1D gradient noise peaks at 0.5, so scale it up to fill -1..1
+    (a + (b - a) * fade) * 2.0
+}
+
+fn sample_and_hold(phase: f32, seed: u32, steps: u32) -> f32 {
+    let (i, _, _) = lattice(phase, steps);
+    hash(seed, i)
+}
+
+// The values of the bounded random walk at each lattice point. The walk is pinned back to zero
+// at the end of the cycle so that it loops, then folded back into -1..1 where it strays.
+fn walk(seed: u32, steps: u32) -> Vec<f32> {
+    let step_size = 2.0 / (steps as f32).sqrt();
+    let increments: Vec<f32> = (0..steps).map(|i| hash(seed, i) * step_size).collect();
+    let total: f32 = increments.iter().sum();
+    let mut value = 0.0;
+    let mut points = Vec::with_capacity(steps as usize);
+    for (i, increment) in increments.iter().enumerate() {
+        points.push(fold(value - total * i as f32 / steps as f32));
+        value += increment;
+    }
+    points
+}
+
+// The most walks cached per thread before the cache is cleared.
+const MAX_CACHED_WALKS: usize = 16;
+
+thread_local! {
+    // The walks are the same on every call for a given seed and number of steps, so they are
+    // generated once and cached by each thread that reads them.
+    static WALKS: RefCell<HashMap<(u32, u32), Vec<f32>>> = RefCell::new(HashMap::new());
+}
+
+// Reflect a value back into the -1..1 range.
+fn fold(value: f32) -> f32 {
+    triangle(fmod(0.25 - value * 0.25, 1.0))
+}
+
+fn random_walk(phase: f32, seed: u32, steps: u32) -> f32 {
+    let steps = steps.max(1);
+    let (i, j, t) = lattice(phase, steps);
+    WALKS.with(|walks| {
+        let mut walks = walks.borrow_mut();
+        if walks.len() >= MAX_CACHED_WALKS && !walks.contains_key(&(seed, steps)) {
+            walks.clear();
+        }
+        let points = walks
+            .entry((seed, steps))
+            .or_insert_with(|| walk(seed, steps));
+        let (a, b) = (points[i as usize], points[j as usize]);
+        a + (b - a) * t
+    })
+}
+
+//------------------ BAND LIMITING
+/// A signal prepared for rendering at audio rate.
+///
+/// The discontinuities of the naive waveforms alias badly once an oscillator reaches audio
+/// frequencies, so they are smoothed with PolyBLEP. The jumps are found once up front by
+/// measuring the signal over a cycle, which covers the edges of any signal, from a square or
+/// sample and hold to the steps of a curve, a rhythm, a formula or a tree of combined signals.
+/// The control rate outputs use `Signal::amp` directly and keep their sharp edges.
+#[derive(Clone, Debug)]
+pub struct BandLimited {
+    signal: Signal,
+    // The discontinuities within a cycle, including the wrap from the end back to the start
+    edges: Vec<Edge>,
+}
+
+/// A discontinuity of a signal, where it jumps from one value to another.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Edge {
+    // Where the signal jumps, 0..1
+    pub phase: f32,
+    // The size of the jump, positive when rising
+    pub jump: f32,
+}
+
+/// The number of points a signal is measured at when searching for its discontinuities.
+pub const EDGE_RESOLUTION: usize = 4096;
+/// Jumps smaller than this aren't worth smoothing.
+pub const MIN_JUMP: f32 = 1e-3;
+
+impl BandLimited {
+    pub fn new(signal: Signal) -> Self {
+        let edges = find_edges(&signal);
+        BandLimited { signal, edges }
+    }
+
+    /// The amplitude at the given phase, where `dt` is the phase increment per sample.
+    pub fn amp(&self, phase: f32, dt: f32) -> f32 {
+        let phase = fmod(phase, 1.0);
+        let mut amp = self.signal.amp(phase);
+        for edge in &self.edges {
+            amp += edge.jump * 0.5 * poly_blep(fmod(phase - edge.phase, 1.0), dt);
+        }
+        amp
+    }
+}
+
+/// Find the discontinuities of the signal over a cycle, including the wrap from the end back to
+/// the start. The cycle is measured at `EDGE_RESOLUTION` points, and each step between
+/// neighbouring points that changes by more than `MIN_JUMP` is narrowed down by bisection. A jump
+/// stays the same size however narrow the step becomes, while a steep but continuous slope
+/// shrinks away.
+pub fn find_edges(signal: &Signal) -> Vec<Edge> {
+    // The signal at a phase in 0..=1, where 1 is read as the start of the next cycle
+    let amp = |x: f32| signal.amp(if x >= 1.0 { 0.0 } else { x });
+    let mut edges = Vec::new();
+    for k in 0..EDGE_RESOLUTION {
+        let mut lo = k as f32 / EDGE_RESOLUTION as f32;
+        let mut hi = (k + 1) as f32 / EDGE_RESOLUTION as f32;
+        let (mut lo_amp, mut hi_amp) = (amp(lo), amp(hi));
+        if (hi_amp - lo_amp).abs() <= MIN_JUMP {
+            continue;
+        }
+        // Halving the step 32 times narrows it to well below the resolution of an f32 phase.
+        for _ in 0..32 {
+            let mid = (lo + hi) * 0.5;
+            if mid <= lo || mid >= hi {
+                break;
+            }
+            let mid_amp = amp(mid);
+            if (mid_amp - lo_amp).abs() >= (hi_amp - mid_amp).abs() {
+                hi = mid;
+                hi_amp = mid_amp;
+            } else {
+                lo = mid;
+                lo_amp = mid_amp;
+            }
+        }
+        let jump = hi_amp - lo_amp;
+        if jump.abs() > MIN_JUMP {
+            let phase = fmod(hi, 1.0);
+            edges.push(Edge { phase, jump });
+        }
+    }
+    edges
+}
+
+// The PolyBLEP residual for a jump of -2 at phase 0, where `t` is the phase since the jump.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    let dt = dt.abs().min(0.5);
+    if dt == 0.0 {
+        0.0
+    } else if t < dt {
+        let x = t / dt;
+        x + x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + x + x + 1.0
+    } else {
+        0.0
+    }
 }
 
 //------------------ EASINGS
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EasingType {
-    BackIn,
-    BackInOut,
-    BackInOutS,
-    BackInS,
-    BackOut,
-    BackOutS,
+    // How far the Back easings travel past their start and end points
+    BackIn { overshoot: f32 },
+    BackInOut { overshoot: f32 },
+    BackInOutS { overshoot: f32 },
+    BackInS { overshoot: f32 },
+    BackOut { overshoot: f32 },
+    BackOutS { overshoot: f32 },
     BounceIn,
     BounceInOut,
     BounceOut,
@@ -168,9 +499,10 @@ pub enum EasingType {
     CubicIn,
     CubicInOut,
     CubicOut,
-    ElasticIn,
-    ElasticInOut,
-    ElasticOut,
+    // The amplitude and period of the Elastic oscillation
+    ElasticIn { amplitude: f32, period: f32 },
+    ElasticInOut { amplitude: f32, period: f32 },
+    ElasticOut { amplitude: f32, period: f32 },
     ExpoIn,
     ExpoInOut,
     ExpoOut,
@@ -186,12 +518,62 @@ pub enum EasingType {
     SineIn,
     SineInOut,
     SineOut,
+    // A generic `phase^exponent` curve
+    PowerIn { exponent: f32 },
+    PowerInOut { exponent: f32 },
+    PowerOut { exponent: f32 },
+    // The two control points of a CSS style `cubic-bezier(x1, y1, x2, y2)`. The x values are
+    // kept within 0..1 so that the curve only moves forward in time.
+    CubicBezier { x1: f32, y1: f32, x2: f32, y2: f32 },
 }
 
 impl EasingType {
     pub fn amp(&self, phase: f32) -> f32 {
         ease_lfo(*self, fmod(phase, 1.0)) * 2.0 - 1.0
     }
+
+    /// The name of the variant without its parameters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EasingType::BackIn { .. } => "BackIn",
+            EasingType::BackInOut { .. } => "BackInOut",
+            EasingType::BackInOutS { .. } => "BackInOutS",
+            EasingType::BackInS { .. } => "BackInS",
+            EasingType::BackOut { .. } => "BackOut",
+            EasingType::BackOutS { .. } => "BackOutS",
+            EasingType::BounceIn => "BounceIn",
+            EasingType::BounceInOut => "BounceInOut",
+            EasingType::BounceOut => "BounceOut",
+            EasingType::CircIn => "CircIn",
+            EasingType::CircInOut => "CircInOut",
+            EasingType::CircOut => "CircOut",
+            EasingType::CubicIn => "CubicIn",
+            EasingType::CubicInOut => "CubicInOut",
+            EasingType::CubicOut => "CubicOut",
+            EasingType::ElasticIn { .. } => "ElasticIn",
+            EasingType::ElasticInOut { .. } => "ElasticInOut",
+            EasingType::ElasticOut { .. } => "ElasticOut",
+            EasingType::ExpoIn => "ExpoIn",
+            EasingType::ExpoInOut => "ExpoInOut",
+            EasingType::ExpoOut => "ExpoOut",
+            EasingType::QuadIn => "QuadIn",
+            EasingType::QuadInOut => "QuadInOut",
+            EasingType::QuadOut => "QuadOut",
+            EasingType::QuartIn => "QuartIn",
+            EasingType::QuartInOut => "QuartInOut",
+            EasingType::QuartOut => "QuartOut",
+            EasingType::QuintIn => "QuintIn",
+            EasingType::QuintInOut => "QuintInOut",
+            EasingType::QuintOut => "QuintOut",
+            EasingType::SineIn => "SineIn",
+            EasingType::SineInOut => "SineInOut",
+            EasingType::SineOut => "SineOut",
+            EasingType::PowerIn { .. } => "PowerIn",
+            EasingType::PowerInOut { .. } => "PowerInOut",
+            EasingType::PowerOut { .. } => "PowerOut",
+            EasingType::CubicBezier { .. } => "CubicBezier",
+        }
+    }
 }
 
 pub fn ease_lfo(ease_type: EasingType, phase: f32) -> f32 {
@@ -201,14 +583,16 @@ pub fn ease_lfo(ease_type: EasingType, phase: f32) -> f32 {
 
     match ease_type {
         // Back
-        // the back easing equation can receive an extra parameter in the _s functions
-        // that controls how much the easing goes forward or backwards
-        EasingType::BackIn => back::ease_in(phase, from, distance, duration),
-        EasingType::BackInOut => back::ease_in_out(phase, from, distance, duration),
-        EasingType::BackInOutS => back::ease_in_out_s(phase, from, distance, duration, 0.8),
-        EasingType::BackInS => back::ease_in_s(phase, from, distance, duration, 0.8),
-        EasingType::BackOut => back::ease_out(phase, from, distance, duration),
-        EasingType::BackOutS => back::ease_out_s(phase, from, distance, duration, 0.8),
+        // the overshoot controls how much the easing goes forward or backwards
+        EasingType::BackIn { overshoot } | EasingType::BackInS { overshoot } => {
+            back::ease_in_s(phase, from, distance, duration, overshoot)
+        }
+        EasingType::BackInOut { overshoot } | EasingType::BackInOutS { overshoot } => {
+            back::ease_in_out_s(phase, from, distance, duration, overshoot)
+        }
+        EasingType::BackOut { overshoot } | EasingType::BackOutS { overshoot } => {
+            back::ease_out_s(phase, from, distance, duration, overshoot)
+        }
         // Bounce
         EasingType::BounceIn => bounce::ease_in(phase, from, distance, duration),
         EasingType::BounceInOut => bounce::ease_in_out(phase, from, distance, duration),
@@ -222,9 +606,9 @@ pub fn ease_lfo(ease_type: EasingType, phase: f32) -> f32 {
         EasingType::CubicInOut => cubic::ease_in_out(phase, from, distance, duration),
         EasingType::CubicOut => cubic::ease_out(phase, from, distance, duration),
         // Elastic
-        EasingType::ElasticIn => elastic::ease_in(phase, from, distance, duration),
-        EasingType::ElasticInOut => elastic::ease_in_out(phase, from, distance, duration),
-        EasingType::ElasticOut => elastic::ease_out(phase, from, distance, duration),
+        EasingType::ElasticIn { amplitude, period } => elastic_in(phase, amplitude, period),
+        EasingType::ElasticInOut { amplitude, period } => elastic_in_out(phase, amplitude, period),
+        EasingType::ElasticOut { amplitude, period } => elastic_out(phase, amplitude, period),
         // Expo
         EasingType::ExpoIn => expo::ease_in(phase, from, distance, duration),
         EasingType::ExpoInOut => expo::ease_in_out(phase, from, distance, duration),
@@ -236,14 +620,415 @@ pub fn ease_lfo(ease_type: EasingType, phase: f32) -> f32 {
         // Quart
         EasingType::QuartIn => quart::ease_in(phase, from, distance, duration),
         EasingType::QuartInOut => quart::ease_in_out(phase, from, distance, duration),
-        EasingType::QuartOut => quart::ease_in_out(phase, from, distance, duration),
+        EasingType::QuartOut => quart::ease_out(phase, from, distance, duration),
         // Quint
         EasingType::QuintIn => quint::ease_in(phase, from, distance, duration),
         EasingType::QuintInOut => quint::ease_in_out(phase, from, distance, duration),
-        EasingType::QuintOut => quint::ease_in_out(phase, from, distance, duration),
+        EasingType::QuintOut => quint::ease_out(phase, from, distance, duration),
         // Sine
         EasingType::SineIn => sine::ease_in(phase, from, distance, duration),
         EasingType::SineInOut => sine::ease_in_out(phase, from, distance, duration),
-        EasingType::SineOut => sine::ease_in_out(phase, from, distance, duration),
+        EasingType::SineOut => sine::ease_out(phase, from, distance, duration),
+        // Power
+        EasingType::PowerIn { exponent } => power_in(phase, exponent),
+        EasingType::PowerInOut { exponent } => power_in_out(phase, exponent),
+        EasingType::PowerOut { exponent } => power_out(phase, exponent),
+        // Bezier
+        EasingType::CubicBezier { x1, y1, x2, y2 } => cubic_bezier(phase, x1, y1, x2, y2),
+    }
+}
+
+// Penner's elastic equations with the amplitude and period exposed, over a 0..1 range.
+// An amplitude below 1.0 can't reach the end points so it is raised to 1.0, as in the original.
+fn elastic_shift(amplitude: f32, period: f32) -> (f32, f32) {
+    if amplitude < 1.0 {
+        (1.0, period / 4.0)
+    } else {
+        (amplitude, period / (PI * 2.0) * (1.0 / amplitude).asin())
+    }
+}
+
+fn elastic_in(phase: f32, amplitude: f32, period: f32) -> f32 {
+    if phase <= 0.0 || phase >= 1.0 {
+        return phase.clamp(0.0, 1.0);
+    }
+    let (a, s) = elastic_shift(amplitude, period);
+    let t = phase - 1.0;
+    -(a * 2.0.powf(10.0 * t)) * ((t - s) * (PI * 2.0) / period).sin()
+}
+
+fn elastic_out(phase: f32, amplitude: f32, period: f32) -> f32 {
+    if phase <= 0.0 || phase >= 1.0 {
+        return phase.clamp(0.0, 1.0);
+    }
+    let (a, s) = elastic_shift(amplitude, period);
+    a * 2.0.powf(-10.0 * phase) * ((phase - s) * (PI * 2.0) / period).sin() + 1.0
+}
+
+fn elastic_in_out(phase: f32, amplitude: f32, period: f32) -> f32 {
+    if phase <= 0.0 || phase >= 1.0 {
+        return phase.clamp(0.0, 1.0);
+    }
+    let (a, s) = elastic_shift(amplitude, period);
+    let t = phase * 2.0 - 1.0;
+    let wave = ((t - s) * (PI * 2.0) / period).sin();
+    if t < 0.0 {
+        -0.5 * a * 2.0.powf(10.0 * t) * wave
+    } else {
+        0.5 * a * 2.0.powf(-10.0 * t) * wave + 1.0
+    }
+}
+
+fn power_in(phase: f32, exponent: f32) -> f32 {
+    phase.powf(exponent)
+}
+
+fn power_out(phase: f32, exponent: f32) -> f32 {
+    1.0 - (1.0 - phase).powf(exponent)
+}
+
+fn power_in_out(phase: f32, exponent: f32) -> f32 {
+    if phase < 0.5 {
+        (phase * 2.0).powf(exponent) * 0.5
+    } else {
+        1.0 - (2.0 - phase * 2.0).powf(exponent) * 0.5
+    }
+}
+
+// The y value of the bezier from (0, 0) to (1, 1) at the given x, as CSS `cubic-bezier` does.
+// The curve is parametric in t, so x(t) = phase is solved first with Newton's method, falling
+// back to bisection where the slope is too flat.
+fn cubic_bezier(phase: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+    let bezier = |t: f32, p1: f32, p2: f32| {
+        let u = 1.0 - t;
+        3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+    };
+    let slope = |t: f32, p1: f32, p2: f32| {
+        let u = 1.0 - t;
+        3.0 * u * u * p1 + 6.0 * u * t * (p2 - p1) + 3.0 * t * t * (1.0 - p2)
+    };
+    let phase = phase.clamp(0.0, 1.0);
+
+    // Newton's method, keeping t within the curve so a flat slope can't throw it outside.
+    let mut t = phase;
+    for _ in 0..8 {
+        let error = bezier(t, x1, x2) - phase;
+        if error.abs() < 1e-6 {
+            return bezier(t, y1, y2);
+        }
+        let d = slope(t, x1, x2);
+        if d.abs() < 1e-6 {
+            break;
+        }
+        t = (t - error / d).clamp(0.0, 1.0);
+    }
+
+    let (mut lo, mut hi) = (0.0, 1.0);
+    t = phase;
+    for _ in 0..32 {
+        let x = bezier(t, x1, x2);
+        if (x - phase).abs() < 1e-6 {
+            break;
+        }
+        if x < phase {
+            lo = t;
+        } else {
+            hi = t;
+        }
+        t = (lo + hi) * 0.5;
+    }
+    bezier(t, y1, y2)
+}
+
+//------------------ CURVES
+/// A user defined signal built from a list of breakpoints over a single cycle.
+///
+/// Each breakpoint describes how the curve travels from its value to the value of the next
+/// breakpoint. The last breakpoint travels to the first, so the curve loops.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Breakpoint>", into = "Vec<Breakpoint>")]
+pub struct Curve {
+    // Sorted by phase
+    points: Cow<'static, [Breakpoint]>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    // The position of the breakpoint within the cycle, 0..1
+    pub phase: f32,
+    // The value of the signal at the breakpoint, -1..1
+    pub value: f32,
+    // How to travel to the next breakpoint
+    pub interpolation: Interpolation,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    // Hold the value until the next breakpoint
+    Step,
+    Linear,
+    Ease(EasingType),
+}
+
+/// A breakpoint of the curve has a phase or value that isn't a finite number.
+#[derive(Debug)]
+pub struct InvalidCurve;
+
+impl Curve {
+    /// Construct a curve from the given breakpoints, sorting them by phase
+    pub fn new(mut points: Vec<Breakpoint>) -> Self {
+        // Sort any NaN phases last, as a plain `partial_cmp` has no order for them.
+        points.sort_by(|a, b| {
+            let nan_last = || a.phase.is_nan().cmp(&b.phase.is_nan());
+            a.phase.partial_cmp(&b.phase).unwrap_or_else(nan_last)
+        });
+        Curve {
+            points: Cow::Owned(points),
+        }
+    }
+
+    /// The breakpoints of the curve, sorted by phase
+    pub fn points(&self) -> &[Breakpoint] {
+        &self.points
+    }
+
+    pub fn amp(&self, phase: f32) -> f32 {
+        let points = &self.points[..];
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 0.0,
+        };
+
+        // Find the segment containing the phase, wrapping from the last breakpoint to the first.
+        let phase = fmod(phase, 1.0);
+        let (from, to, start, end) = match points.iter().rposition(|p| p.phase <= phase) {
+            Some(i) if i + 1 < points.len() => {
+                let (from, to) = (&points[i], &points[i + 1]);
+                (from, to, from.phase, to.phase)
+            }
+            Some(_) => (last, first, last.phase, first.phase + 1.0),
+            None => (last, first, last.phase - 1.0, first.phase),
+        };
+
+        let t = if end > start {
+            (phase - start) / (end - start)
+        } else {
+            0.0
+        };
+        from.interpolation.interpolate(from.value, to.value, t)
+    }
+}
+
+impl std::convert::TryFrom<Vec<Breakpoint>> for Curve {
+    type Error = InvalidCurve;
+    fn try_from(points: Vec<Breakpoint>) -> Result<Self, Self::Error> {
+        // Reject the breakpoints of a hand edited preset that aren't numbers.
+        if points
+            .iter()
+            .any(|p| !p.phase.is_finite() || !p.value.is_finite())
+        {
+            return Err(InvalidCurve);
+        }
+        Ok(Curve::new(points))
+    }
+}
+
+impl From<Curve> for Vec<Breakpoint> {
+    fn from(curve: Curve) -> Self {
+        curve.points.into_owned()
+    }
+}
+
+impl Interpolation {
+    /// Interpolate between the values `a` and `b` where `t` is in the range 0..1
+    pub fn interpolate(&self, a: f32, b: f32, t: f32) -> f32 {
+        match self {
+            Interpolation::Step => a,
+            Interpolation::Linear => a + (b - a) * t,
+            Interpolation::Ease(ease_type) => a + (b - a) * ease_lfo(*ease_type, t),
+        }
+    }
+}
+
+impl fmt::Display for InvalidCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "curve breakpoints must be finite numbers")
+    }
+}
+
+impl std::error::Error for InvalidCurve {}
+
+//------------------ COMBINATIONS
+/// A node in a tree of signals, combining the output of its children.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Combine {
+    Sum(Vec<Signal>),
+    Product(Vec<Signal>),
+    Min(Vec<Signal>),
+    Max(Vec<Signal>),
+    // Scale and offset a signal, useful for weighting the children of a `Sum`
+    Scale {
+        signal: Box<Signal>,
+        gain: f32,
+        bias: f32,
+    },
+    // Blend from `a` to `b` as `mix` goes from 0 to 1
+    Crossfade {
+        a: Box<Signal>,
+        b: Box<Signal>,
+        mix: f32,
+    },
+    // Offset the phase of the carrier by the modulator, `depth` is measured in cycles
+    PhaseMod {
+        carrier: Box<Signal>,
+        modulator: Box<Signal>,
+        depth: f32,
+    },
+}
+
+impl Combine {
+    pub fn amp(&self, phase: f32) -> f32 {
+        match self {
+            Combine::Sum(signals) => signals.iter().map(|s| s.amp(phase)).sum(),
+            Combine::Product(signals) => signals.iter().map(|s| s.amp(phase)).product(),
+            Combine::Min(signals) => signals
+                .iter()
+                .map(|s| s.amp(phase))
+                .fold(None, |min: Option<f32>, amp| {
+                    Some(min.map_or(amp, |m| m.min(amp)))
+                })
+                .unwrap_or(0.0),
+            Combine::Max(signals) => signals
+                .iter()
+                .map(|s| s.amp(phase))
+                .fold(None, |max: Option<f32>, amp| {
+                    Some(max.map_or(amp, |m| m.max(amp)))
+                })
+                .unwrap_or(0.0),
+            Combine::Scale { signal, gain, bias } => signal.amp(phase) * gain + bias,
+            Combine::Crossfade { a, b, mix } => {
+                let (a, b) = (a.amp(phase), b.amp(phase));
+                a + (b - a) * mix
+            }
+            Combine::PhaseMod {
+                carrier,
+                modulator,
+                depth,
+            } => carrier.amp(fmod(phase + modulator.amp(phase) * depth, 1.0)),
+        }
+    }
+}
+
+/// Some example combinations of the built-in signals, listed after `ALL` in the GUI.
+pub fn combinations() -> Vec<Signal> {
+    let scale = |signal: Signal, gain: f32| {
+        Signal::Combine(Combine::Scale {
+            signal: Box::new(signal),
+            gain,
+            bias: 0.0,
+        })
+    };
+    vec![
+        // A sine with a bounce on top
+        Signal::Combine(Combine::Sum(vec![
+            scale(Signal::SINE, 0.7),
+            scale(Signal::BOUNCE_OUT, 0.3),
+        ])),
+        // A sine ring modulated by a triangle
+        Signal::Combine(Combine::Product(vec![Signal::SINE, Signal::TRIANGLE])),
+        Signal::Combine(Combine::Crossfade {
+            a: Box::new(Signal::TRIANGLE),
+            b: Box::new(Signal::ELASTIC_OUT),
+            mix: 0.5,
+        }),
+        Signal::Combine(Combine::PhaseMod {
+            carrier: Box::new(Signal::SINE),
+            modulator: Box::new(Signal::TRIANGLE),
+            depth: 0.25,
+        }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The phase increment per sample of a 1600 Hz oscillator at 44.1 kHz.
+    const DT: f32 = 1600.0 / 44100.0;
+
+    fn band_limited(signal: Signal) -> BandLimited {
+        BandLimited::new(signal)
+    }
+
+    #[test]
+    fn saw_is_smoothed_around_the_wrap() {
+        let saw = band_limited(Signal::SAWTOOTH);
+        // The naive saw jumps from -1 to 1, the band-limited one meets in the middle.
+        assert!(saw.amp(0.0, DT).abs() < 1e-3);
+        assert!(saw.amp(1.0 - 1e-6, DT).abs() < 1e-3);
+        assert!(saw.amp(DT * 0.5, DT) < Signal::SAWTOOTH.amp(DT * 0.5));
+        assert!(saw.amp(1.0 - DT * 0.5, DT) > Signal::SAWTOOTH.amp(1.0 - DT * 0.5));
+        // Away from the wrap it is the naive saw.
+        for &phase in &[0.1, 0.25, 0.5, 0.9] {
+            assert_eq!(saw.amp(phase, DT), Signal::SAWTOOTH.amp(phase));
+        }
+    }
+
+    #[test]
+    fn square_is_smoothed_at_both_edges() {
+        let square = band_limited(Signal::SQUARE);
+        // The rising edge half way through and the falling edge at the wrap.
+        for &edge in &[0.5, 0.0] {
+            assert!(square.amp(edge, DT).abs() < 1e-3);
+            assert!(square.amp(fmod(edge - 1e-6, 1.0), DT).abs() < 1e-3);
+        }
+        for &phase in &[0.1, 0.25, 0.75, 0.9] {
+            assert_eq!(square.amp(phase, DT), Signal::SQUARE.amp(phase));
+        }
+    }
+
+    #[test]
+    fn square_without_edges_is_left_alone() {
+        for &pulse_width in &[0.0, 1.0] {
+            let signal = Signal::Lfo(LfoType::Square { pulse_width });
+            let square = band_limited(signal.clone());
+            assert!(square.edges.is_empty());
+            for i in 0..100 {
+                let phase = i as f32 / 100.0;
+                assert_eq!(square.amp(phase, DT), signal.amp(phase));
+            }
+        }
+    }
+
+    #[test]
+    fn edges_are_found_within_combinations_and_steps() {
+        let sum = Signal::Combine(Combine::Sum(vec![Signal::SINE, Signal::SQUARE]));
+        assert_eq!(band_limited(sum).edges.len(), 2);
+        let steps = Signal::Lfo(LfoType::SampleAndHold { seed: 3, steps: 8 });
+        assert_eq!(band_limited(steps).edges.len(), 8);
+        // The curve holds its last breakpoint and steps back to the first at the wrap.
+        assert_eq!(band_limited(Signal::CURVE).edges.len(), 1);
+        // Each of the three pulses rises and falls.
+        assert_eq!(band_limited(Signal::EUCLIDEAN).edges.len(), 6);
+        assert!(band_limited(Signal::SINE).edges.is_empty());
+        assert!(band_limited(Signal::RANDOM_WALK).edges.is_empty());
+    }
+
+    #[test]
+    fn cubic_bezier_stays_within_its_handles() {
+        let handles = [
+            (0.0, 0.0, 1.0, 1.0),
+            (0.9, -0.5, 0.1, 1.5),
+            (1.0, 0.0, 0.0, 1.0),
+            (0.25, 0.1, 0.25, 1.0),
+        ];
+        for &(x1, y1, x2, y2) in &handles {
+            let range = y1.min(y2).min(0.0) - 1e-4..=y1.max(y2).max(1.0) + 1e-4;
+            for i in 0..=100 {
+                let y = cubic_bezier(i as f32 / 100.0, x1, y1, x2, y2);
+                assert!(range.contains(&y), "{} {} {} {} {}", x1, y1, x2, y2, y);
+            }
+        }
     }
 }
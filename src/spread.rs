@@ -0,0 +1,101 @@
+// Speed and phase spread module
+//
+// Gives each oscillator its own speed multiplier and phase offset, on top of the shm's shared
+// `hz` and offsets. A distribution places each oscillator between none of the spread and all of
+// it, so that every pixel can run at a slightly different speed.
+use crate::signals::{self, EasingType, DEFAULT_BEZIER};
+
+/// Each distribution with its default parameters, in the order they are listed in the GUI.
+pub const ALL: &[Distribution] = &[
+    Distribution::Linear,
+    Distribution::Exponential,
+    Distribution::Random { seed: 0 },
+    Distribution::CentreOut,
+    Distribution::Curve(EasingType::CubicBezier {
+        x1: DEFAULT_BEZIER[0],
+        y1: DEFAULT_BEZIER[1],
+        x2: DEFAULT_BEZIER[2],
+        y2: DEFAULT_BEZIER[3],
+    }),
+];
+
+// How steeply the exponential distribution rises towards the last oscillator.
+const EXPONENT: f32 = 4.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Distribution {
+    // Rises evenly from the first oscillator to the last
+    Linear,
+    // Rises slowly at first and quickly towards the last oscillator
+    Exponential,
+    // A random amount for each oscillator, the same for the same seed
+    Random { seed: u32 },
+    // Rises from the centre of the strip out to both ends
+    CentreOut,
+    // Rises along the given easing from the first oscillator to the last
+    Curve(EasingType),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spread {
+    pub distribution: Distribution,
+    // The speed of the oscillator with all of the spread, as a fraction of the shm `hz` added
+    // to its own, e.g. 0.5 runs it half as fast again
+    pub speed: f32,
+    // The phase offset of the oscillator with all of the spread, in cycles
+    pub phase: f32,
+}
+
+impl Distribution {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Distribution::Linear => "Linear",
+            Distribution::Exponential => "Exponential",
+            Distribution::Random { .. } => "Random",
+            Distribution::CentreOut => "Centre Out",
+            Distribution::Curve(_) => "Curve",
+        }
+    }
+
+    /// How much of the spread the `i`th of `size` oscillators gets, 0..1
+    pub fn amount(&self, i: usize, size: usize) -> f32 {
+        let t = match size {
+            0 | 1 => 0.0,
+            _ => i as f32 / (size - 1) as f32,
+        };
+        match *self {
+            Distribution::Linear => t,
+            Distribution::Exponential => ((t * EXPONENT).exp() - 1.0) / (EXPONENT.exp() - 1.0),
+            Distribution::Random { seed } => signals::hash(seed, i as u32) * 0.5 + 0.5,
+            Distribution::CentreOut => (t * 2.0 - 1.0).abs(),
+            Distribution::Curve(ease_type) => signals::ease_lfo(ease_type, t),
+        }
+    }
+}
+
+impl Spread {
+    /// No spread, every oscillator runs at the shm `hz` with no extra offset
+    pub fn new() -> Self {
+        Spread {
+            distribution: Distribution::Linear,
+            speed: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    /// The multiplier of the shm `hz` for the `i`th of `size` oscillators
+    pub fn speed_at(&self, i: usize, size: usize) -> f32 {
+        1.0 + self.speed * self.distribution.amount(i, size)
+    }
+
+    /// The phase offset in cycles of the `i`th of `size` oscillators
+    pub fn phase_at(&self, i: usize, size: usize) -> f32 {
+        self.phase * self.distribution.amount(i, size)
+    }
+}
+
+impl Default for Spread {
+    fn default() -> Self {
+        Spread::new()
+    }
+}
@@ -0,0 +1,94 @@
+// Damped spring module
+//
+// A mass-spring-damper per oscillator. Each spring is pulled towards its target, either the
+// shm phase of the same oscillator or a fixed value, and can be kicked by a trigger. The
+// positions are in the same -1..1 range as the shm phases, with overshoot beyond it.
+use crate::resample;
+use std::time::Duration;
+
+/// The default stiffness, giving a lively bounce at the default mass.
+pub const DEFAULT_STIFFNESS: f32 = 120.0;
+/// The default damping, enough to settle within a second or so.
+pub const DEFAULT_DAMPING: f32 = 6.0;
+pub const DEFAULT_MASS: f32 = 1.0;
+
+// The largest step the simulation takes, so that stiff springs stay stable at low frame rates.
+const MAX_STEP: f32 = 1.0 / 240.0;
+// Longer frames than this, such as after a stall, are simulated as this long.
+const MAX_DT: f32 = 0.25;
+
+pub struct Spring {
+    positions: Vec<f32>,
+    velocities: Vec<f32>,
+    // How strongly each spring is pulled towards its target
+    pub stiffness: f32,
+    // How quickly the motion dies away
+    pub damping: f32,
+    pub mass: f32,
+    // A value for every spring to settle at, instead of following its shm phase
+    pub target: Option<f32>,
+}
+
+impl Spring {
+    pub fn new() -> Self {
+        Spring {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            stiffness: DEFAULT_STIFFNESS,
+            damping: DEFAULT_DAMPING,
+            mass: DEFAULT_MASS,
+            target: None,
+        }
+    }
+
+    /// Kick every spring with the given velocity.
+    pub fn trigger(&mut self, impulse: f32) {
+        for v in self.velocities.iter_mut() {
+            *v += impulse;
+        }
+    }
+
+    /// Advance the springs by `dt` towards the given targets, one per spring. The springs are
+    /// resampled along the strip to match the number of targets, starting at their targets if
+    /// there were none.
+    pub fn update(&mut self, targets: &[f32], dt: Duration) {
+        if self.positions.len() != targets.len() {
+            self.positions = match self.positions.is_empty() {
+                true => targets.to_vec(),
+                false => resample::resample(&self.positions, targets.len()),
+            };
+            self.velocities = resample::resample(&self.velocities, targets.len());
+        }
+
+        let dt = (dt.as_secs_f64() as f32).min(MAX_DT);
+        let steps = (dt / MAX_STEP).ceil().max(1.0);
+        let h = dt / steps;
+        let mass = self.mass.max(0.01);
+
+        for ((x, v), &target) in self
+            .positions
+            .iter_mut()
+            .zip(self.velocities.iter_mut())
+            .zip(targets)
+        {
+            let target = self.target.unwrap_or(target);
+            // Semi-implicit Euler, which keeps the spring's energy bounded.
+            for _ in 0..steps as usize {
+                let force = -self.stiffness * (*x - target) - self.damping * *v;
+                *v += force / mass * h;
+                *x += *v * h;
+            }
+        }
+    }
+
+    /// The position of each spring
+    pub fn positions(&self) -> &[f32] {
+        &self.positions
+    }
+}
+
+impl Default for Spring {
+    fn default() -> Self {
+        Spring::new()
+    }
+}
@@ -0,0 +1,78 @@
+// Symmetry module
+//
+// Folds the strip into mirrored or repeated segments, the way lighting consoles do. The shm
+// computes a single pattern, and each position along the strip reads the pattern from the
+// place its segment maps it to.
+use nannou::math::fmod;
+
+/// The direction the pattern travels within each segment. A single segment has no inner edge,
+/// so the direction only applies to two or more.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    // The pattern starts at the outer edge of each segment and travels inwards
+    EdgesIn,
+    // The pattern starts at the inner edge of each segment and travels outwards
+    CentreOut,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Symmetry {
+    // The number of segments the pattern is repeated across, 1 for none
+    pub segments: u32,
+    // Reflect every other segment, kaleidoscope style, rather than repeating them
+    pub reflect: bool,
+    pub direction: Direction,
+    // Run the whole strip backwards
+    pub reverse: bool,
+    // Shift the origin of the pattern along the strip, 0..1
+    pub rotation: f32,
+}
+
+impl Symmetry {
+    /// A single segment, the pattern running once along the strip
+    pub fn new() -> Self {
+        Symmetry {
+            segments: 1,
+            reflect: true,
+            direction: Direction::EdgesIn,
+            reverse: false,
+            rotation: 0.0,
+        }
+    }
+
+    /// The number of positions in the pattern for a strip of `size`
+    pub fn pattern_len(&self, size: usize) -> usize {
+        let segments = self.segments.max(1) as usize;
+        (size + segments - 1) / segments
+    }
+
+    /// The position in the pattern, 0..1, read by the position `t` along the strip, 0..1
+    pub fn fold(&self, t: f32) -> f32 {
+        let t = if self.reverse { 1.0 - t } else { t };
+        let t = fmod(t + self.rotation, 1.0);
+        let x = t * self.segments.max(1) as f32;
+        let segment = x.floor() as u32;
+        let u = x - segment as f32;
+        let u = if self.reflect && segment % 2 == 1 {
+            1.0 - u
+        } else {
+            u
+        };
+        match self.direction {
+            Direction::CentreOut if self.segments > 1 => 1.0 - u,
+            _ => u,
+        }
+    }
+
+    /// The index into a pattern of `len` read by the `i`th of `size` positions along the strip
+    pub fn index(&self, i: usize, size: usize, len: usize) -> usize {
+        let t = (i as f32 + 0.5) / size.max(1) as f32;
+        ((self.fold(t) * len as f32) as usize).min(len.max(1) - 1)
+    }
+}
+
+impl Default for Symmetry {
+    fn default() -> Self {
+        Symmetry::new()
+    }
+}
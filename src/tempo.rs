@@ -0,0 +1,165 @@
+// Tempo clock module
+//
+// Counts beats at the BPM so that the shm speed can be set as a musical division and its phase
+// reset on the downbeat. The BPM can be tapped in, and the beat nudged or resynced by hand to
+// follow the music.
+use std::time::Duration;
+
+pub const DEFAULT_BPM: f32 = 120.0;
+pub const DEFAULT_BEATS_PER_BAR: u32 = 4;
+pub const MIN_BPM: f32 = 20.0;
+pub const MAX_BPM: f32 = 300.0;
+
+// Taps further apart than this many seconds start a new tempo.
+const MAX_TAP_GAP: f64 = 2.0;
+// The number of recent taps averaged to find the tempo.
+const MAX_TAPS: usize = 8;
+
+/// A note length, the number of beats a single cycle of the shm takes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Division {
+    pub name: &'static str,
+    pub beats: f32,
+}
+
+/// The divisions in 4/4, from the slowest to the fastest. `T` marks triplets.
+pub const DIVISIONS: &[Division] = &[
+    Division::new("4 Bars", 16.0),
+    Division::new("2 Bars", 8.0),
+    Division::new("1 Bar", 4.0),
+    Division::new("1/2", 2.0),
+    Division::new("1/4", 1.0),
+    Division::new("1/4T", 2.0 / 3.0),
+    Division::new("1/8", 0.5),
+    Division::new("1/8T", 1.0 / 3.0),
+    Division::new("1/16", 0.25),
+    Division::new("1/16T", 1.0 / 6.0),
+];
+
+/// The index of the one bar division within `DIVISIONS`.
+pub const DEFAULT_DIVISION: usize = 2;
+
+pub struct Clock {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    // The number of beats counted since the clock started
+    beat: f64,
+    // The beat at the previous update, to find which beats have just passed
+    last_beat: f64,
+    // The seconds since the clock started, used to time taps
+    time: f64,
+    // The times of the recent taps
+    taps: Vec<f64>,
+}
+
+impl Division {
+    pub const fn new(name: &'static str, beats: f32) -> Self {
+        Division { name, beats }
+    }
+}
+
+impl Clock {
+    pub fn new(bpm: f32) -> Self {
+        Clock {
+            bpm,
+            beats_per_bar: DEFAULT_BEATS_PER_BAR,
+            beat: 0.0,
+            last_beat: 0.0,
+            time: 0.0,
+            taps: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f64();
+        self.time += dt;
+        self.last_beat = self.beat;
+        self.beat += self.bpm as f64 / 60.0 * dt;
+    }
+
+    /// Set the tempo from the average time between recent taps.
+    pub fn tap(&mut self) {
+        if let Some(&last) = self.taps.last() {
+            if self.time - last > MAX_TAP_GAP {
+                self.taps.clear();
+            }
+        }
+        self.taps.push(self.time);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+        if let (Some(first), Some(last)) = (self.taps.first(), self.taps.last()) {
+            if self.taps.len() > 1 && last > first {
+                let interval = (last - first) / (self.taps.len() - 1) as f64;
+                self.bpm = ((60.0 / interval) as f32).clamp(MIN_BPM, MAX_BPM);
+            }
+        }
+    }
+
+    /// Shift the beat forward, or backward for a negative amount, by the given fraction of a beat.
+    pub fn nudge(&mut self, beats: f32) {
+        self.beat += beats as f64;
+    }
+
+    /// Make the nearest downbeat happen now.
+    pub fn resync(&mut self) {
+        let bar = self.beats_per_bar.max(1) as f64;
+        self.beat = (self.beat / bar).round() * bar;
+        self.last_beat = self.beat;
+    }
+
+    /// The speed in cycles per second that makes one cycle last the given division.
+    pub fn hz(&self, division: Division) -> f32 {
+        self.bpm / 60.0 / division.beats
+    }
+
+    /// The bar and the beat within it, counting from zero.
+    pub fn bar_and_beat(&self) -> (u64, u32) {
+        let beat = self.beat.max(0.0).floor() as u64;
+        let bar = self.beats_per_bar.max(1) as u64;
+        (beat / bar, (beat % bar) as u32)
+    }
+
+    /// Whether the last update passed a downbeat that starts a cycle of the given division. A
+    /// division shorter than a bar restarts on every bar.
+    pub fn passed_downbeat(&self, division: Division) -> bool {
+        let period = self.downbeat_period(division);
+        (self.beat / period).floor() != (self.last_beat / period).floor()
+    }
+
+    /// How far through a cycle of the given division the beat is, 0..1, counting cycles from
+    /// the downbeat that last restarted it.
+    pub fn cycle_phase(&self, division: Division) -> f64 {
+        let beat = self.beat.rem_euclid(self.downbeat_period(division));
+        (beat / division.beats as f64).fract()
+    }
+
+    // The beats between the downbeats that restart a cycle of the given division.
+    fn downbeat_period(&self, division: Division) -> f64 {
+        let bar = self.beats_per_bar.max(1) as f64;
+        (division.beats as f64 / bar).ceil().max(1.0) * bar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_phase_follows_nudges_and_resyncs() {
+        let mut clock = Clock::new(DEFAULT_BPM);
+        let quarter = DIVISIONS[4];
+        let two_bars = DIVISIONS[1];
+        clock.update(Duration::from_millis(1250)); // 2.5 beats
+        assert!((clock.cycle_phase(quarter) - 0.5).abs() < 1e-9);
+        assert!((clock.cycle_phase(two_bars) - 2.5 / 8.0).abs() < 1e-9);
+
+        clock.nudge(0.25);
+        assert!((clock.cycle_phase(quarter) - 0.75).abs() < 1e-9);
+
+        // The nearest downbeat is at beat 4, so every cycle restarts now.
+        clock.resync();
+        assert_eq!(clock.cycle_phase(quarter), 0.0);
+        assert_eq!(clock.cycle_phase(DIVISIONS[0]), 0.25);
+    }
+}
@@ -0,0 +1,55 @@
+// Transport module
+//
+// Plays the shm motion forwards or backwards, pauses it, or seeks it to an absolute time. The
+// motion is a function of the transport time, so a frame can be found again by seeking back to
+// the time it was shown at.
+use std::time::Duration;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transport {
+    // Nanoseconds of motion played so far, counted exactly so that the time never drifts from
+    // the frames however long it runs
+    nanos: i64,
+    // Freeze the motion at the current time
+    pub paused: bool,
+    // Play the motion backwards
+    pub reverse: bool,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Transport {
+            nanos: 0,
+            paused: false,
+            reverse: false,
+        }
+    }
+
+    /// Advance the time by `dt` unless paused, or rewind it when reversed.
+    pub fn advance(&mut self, dt: Duration) {
+        if self.paused {
+            return;
+        }
+        let nanos = dt.as_nanos() as i64;
+        match self.reverse {
+            true => self.nanos -= nanos,
+            false => self.nanos += nanos,
+        }
+    }
+
+    /// Jump to the given time in seconds.
+    pub fn seek(&mut self, time: f64) {
+        self.nanos = (time * 1e9).round() as i64;
+    }
+
+    /// The seconds of motion played so far
+    pub fn time(&self) -> f64 {
+        self.nanos as f64 / 1e9
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::new()
+    }
+}
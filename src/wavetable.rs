@@ -0,0 +1,245 @@
+// Wavetable signals loaded from WAV files
+use nannou::math::fmod;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The directory within the assets directory that wavetables are loaded from.
+pub const DIRECTORY: &str = "wavetables";
+
+/// Multi-frame wavetables are made of frames of this many samples, as in most wavetable synths.
+/// Files that aren't a whole number of frames are treated as a single cycle.
+pub const DEFAULT_FRAME_LEN: usize = 2048;
+
+/// A single-cycle or multi-frame waveform read at the shm phase.
+///
+/// The samples are shared between clones, as signals are cloned and compared every frame. Two
+/// tables are equal when they were loaded from the same file with the same settings.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "WavetableSpec", into = "WavetableSpec")]
+pub struct Wavetable {
+    // The WAV file the table was loaded from
+    path: PathBuf,
+    // One or more single cycle waveforms of `frame_len` samples, one after the other
+    samples: Arc<[f32]>,
+    frame_len: usize,
+    // How to read between samples
+    pub interpolation: Interpolation,
+    // The position between the first and last frame, 0..1
+    pub position: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Linear,
+    Cubic,
+}
+
+/// The serialized form of a wavetable. The samples are loaded from the path on deserialization.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WavetableSpec {
+    pub path: PathBuf,
+    pub frame_len: usize,
+    pub interpolation: Interpolation,
+    pub position: f32,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Wav(hound::Error),
+    // The file contains no samples
+    Empty,
+    // The assets directory could not be found
+    NoAssets,
+}
+
+impl Wavetable {
+    /// Load a wavetable from the WAV file at the given path, splitting it into frames of
+    /// `frame_len` samples. Multi-channel files are mixed down to mono.
+    pub fn load<P: AsRef<Path>>(path: P, frame_len: usize) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let max = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / max))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        let channels = spec.channels.max(1) as usize;
+        let mono: Vec<f32> = samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        if mono.is_empty() {
+            return Err(LoadError::Empty);
+        }
+
+        let frame_len = match frame_len > 0 && mono.len() % frame_len == 0 {
+            true => frame_len,
+            false => mono.len(),
+        };
+
+        Ok(Wavetable {
+            path: path.to_path_buf(),
+            samples: mono.into(),
+            frame_len,
+            interpolation: Interpolation::Linear,
+            position: 0.0,
+        })
+    }
+
+    /// Load the wavetable with the given name from the wavetables assets directory. The name is
+    /// the file name without its extension, which may be in any case.
+    pub fn load_from_assets(name: &str) -> Result<Self, LoadError> {
+        let assets = nannou::app::find_assets_path().map_err(|_| LoadError::NoAssets)?;
+        let dir = assets.join(DIRECTORY);
+        let path = wav_paths(&dir)
+            .into_iter()
+            .find(|path| path.file_stem().map(|stem| stem == name).unwrap_or(false))
+            .unwrap_or_else(|| dir.join(format!("{}.wav", name)));
+        Wavetable::load(path, DEFAULT_FRAME_LEN)
+    }
+
+    /// Load every WAV file in the given directory, printing any that fail to load.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Vec<Self> {
+        let mut tables = Vec::new();
+        for path in wav_paths(dir.as_ref()) {
+            match Wavetable::load(&path, DEFAULT_FRAME_LEN) {
+                Ok(table) => tables.push(table),
+                Err(err) => println!("Failed to load wavetable {}: {}", path.display(), err),
+            }
+        }
+        tables
+    }
+
+    /// The file name of the table without its extension
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// The number of frames in the table
+    pub fn num_frames(&self) -> usize {
+        self.samples.len() / self.frame_len
+    }
+
+    // The samples of the `i`th frame
+    fn frame(&self, i: usize) -> &[f32] {
+        &self.samples[i * self.frame_len..(i + 1) * self.frame_len]
+    }
+
+    pub fn amp(&self, phase: f32) -> f32 {
+        // Blend between the two frames either side of the scan position.
+        let num_frames = self.num_frames();
+        let last = (num_frames - 1) as f32;
+        let x = self.position.clamp(0.0, 1.0) * last;
+        let i = (x.floor() as usize).min(num_frames - 1);
+        let j = (i + 1).min(num_frames - 1);
+        let t = x - i as f32;
+
+        let a = self.read(self.frame(i), phase);
+        if t == 0.0 {
+            return a;
+        }
+        let b = self.read(self.frame(j), phase);
+        a + (b - a) * t
+    }
+
+    // Read a single frame at the given phase, wrapping at the frame edges.
+    fn read(&self, frame: &[f32], phase: f32) -> f32 {
+        let len = frame.len();
+        let x = fmod(phase, 1.0) * len as f32;
+        let i = (x.floor() as usize).min(len - 1);
+        let t = x - i as f32;
+        let sample = |offset: isize| frame[(i as isize + offset).rem_euclid(len as isize) as usize];
+
+        match self.interpolation {
+            Interpolation::Linear => {
+                let (a, b) = (sample(0), sample(1));
+                a + (b - a) * t
+            }
+            // Catmull-Rom spline through the four surrounding samples
+            Interpolation::Cubic => {
+                let (y0, y1, y2, y3) = (sample(-1), sample(0), sample(1), sample(2));
+                let c1 = 0.5 * (y2 - y0);
+                let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+                let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+                ((c3 * t + c2) * t + c1) * t + y1
+            }
+        }
+    }
+}
+
+// The WAV files in the given directory, sorted by path.
+fn wav_paths(dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let ext = path.extension().and_then(|ext| ext.to_str());
+            ext.map_or(false, |ext| ext.eq_ignore_ascii_case("wav"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+impl PartialEq for Wavetable {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.frame_len == other.frame_len
+            && self.interpolation == other.interpolation
+            && self.position == other.position
+    }
+}
+
+impl std::convert::TryFrom<WavetableSpec> for Wavetable {
+    type Error = LoadError;
+    fn try_from(spec: WavetableSpec) -> Result<Self, Self::Error> {
+        let mut table = Wavetable::load(&spec.path, spec.frame_len)?;
+        table.interpolation = spec.interpolation;
+        table.position = spec.position;
+        Ok(table)
+    }
+}
+
+impl From<Wavetable> for WavetableSpec {
+    fn from(table: Wavetable) -> Self {
+        WavetableSpec {
+            frame_len: table.frame_len,
+            path: table.path,
+            interpolation: table.interpolation,
+            position: table.position,
+        }
+    }
+}
+
+impl From<hound::Error> for LoadError {
+    fn from(err: hound::Error) -> Self {
+        LoadError::Wav(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Wav(err) => write!(f, "{}", err),
+            LoadError::Empty => write!(f, "the file contains no samples"),
+            LoadError::NoAssets => write!(f, "the assets directory could not be found"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}